@@ -0,0 +1,1009 @@
+use embedded_can::{Frame, Id, StandardId};
+use embedded_hal_async::delay::DelayNs;
+use embedded_hal_async::digital::Wait;
+use embedded_hal_async::spi::{Operation, SpiDevice};
+
+use crate::bitrates;
+use crate::busoff::BusOff;
+use crate::config::CONFIG_REGISTERS_LEN;
+use crate::errorstate::{ERROR_REGISTERS_LEN, StateTracker};
+use crate::filters::FilterBank;
+use crate::interrupt::{self, Service, service};
+use crate::registers::*;
+use crate::rxorder::RxOrder;
+use crate::snapshot::REGISTER_COUNT;
+use crate::variant::{ChipVariant, Mcp2515Features};
+use crate::{
+    ABORT_ALL, ABORT_POLLS, APPLY_CONFIG_TIMEOUT_MS, AcceptanceFilter, BITRATE_DETECTION_FLAGS,
+    BUS_OFF_MODE_POLLS, CanFrame, Config, ConfigRegisters, ERROR_FLAG, Error, ErrorState, IdHeader,
+    Instruction, InterruptError, InterruptEvent, MESSAGE_ERROR_FLAG, NoDelay, ONE_SHOT_MODE,
+    OVERFLOW_FLAGS, POLL_INTERVAL_US, RESET_DELAY_US, RESET_VALUES, RegisterSnapshot, RxBuffer,
+    StateTransition, TXREQ, Transmission, TxBuffer, TxStatus, Variant, VerifyError,
+    overflowed_rx_buffer, pending_tx_buffer, polls,
+};
+
+/// Either a MCP2510, MCP2515 or MCP25625 CAN controller driven by an async SPI device
+///
+/// Async counterpart of [`crate::MCP25xx`]. Both drivers issue the same SPI transactions.
+///
+/// ```
+/// # use mcp25xx::doctesthelper::{FakeSPI, NoOpDelay};
+/// use embedded_can::{Frame, StandardId};
+/// use mcp25xx::asynch::MCP25xx;
+/// use mcp25xx::bitrates::clock_16mhz::CNF_500K_BPS;
+/// use mcp25xx::registers::OperationMode;
+/// use mcp25xx::{CanFrame, Config};
+///
+/// # async fn run() {
+/// # let spi = FakeSPI::default();
+/// # let mut delay = NoOpDelay;
+/// // spi is a struct implementing embedded_hal_async::spi::SpiDevice.
+/// // delay is a struct implementing embedded_hal_async::delay::DelayNs.
+///
+/// let mut mcp25xx = MCP25xx::new(spi);
+///
+/// let config = Config::default()
+///     .mode(OperationMode::NormalOperation)
+///     .bitrate(CNF_500K_BPS);
+/// mcp25xx.apply_config(&config, &mut delay).await.unwrap();
+///
+/// let frame = CanFrame::new(StandardId::new(123).unwrap(), &[1, 2, 3]).unwrap();
+/// mcp25xx.try_transmit(&frame).await.unwrap();
+/// # }
+/// ```
+pub struct MCP25xx<SPI: SpiDevice, V: ChipVariant = Variant> {
+    pub spi: SPI,
+    rx_order: RxOrder,
+    state_tracker: StateTracker,
+    bus_off: BusOff,
+    replacing: Option<TxBuffer>,
+    variant: V,
+}
+
+impl<SPI: SpiDevice> MCP25xx<SPI> {
+    pub fn new(spi: SPI) -> Self {
+        MCP25xx::with_variant(spi, Variant::default())
+    }
+
+    /// See [`crate::MCP25xx::detect_variant`]
+    pub async fn detect_variant(&mut self) -> Result<Variant, SPI::Error> {
+        let canctrl: CANCTRL = self.read_register().await?;
+        self.modify_register(CANCTRL::new().with_osm(true), ONE_SHOT_MODE)
+            .await?;
+        let probe: CANCTRL = self.read_register().await?;
+        self.modify_register(canctrl, ONE_SHOT_MODE).await?;
+        self.variant = if probe.osm() {
+            Variant::MCP2515
+        } else {
+            Variant::MCP2510
+        };
+        Ok(self.variant)
+    }
+}
+
+impl<SPI: SpiDevice, V: ChipVariant> MCP25xx<SPI, V> {
+    /// See [`crate::MCP25xx::with_variant`]
+    pub fn with_variant(spi: SPI, variant: V) -> Self {
+        MCP25xx {
+            spi,
+            rx_order: RxOrder::default(),
+            state_tracker: StateTracker::default(),
+            bus_off: BusOff::default(),
+            replacing: None,
+            variant,
+        }
+    }
+
+    pub fn variant(&self) -> V {
+        self.variant
+    }
+
+    /// See [`crate::MCP25xx::apply_config`]
+    pub async fn apply_config(
+        &mut self,
+        config: &Config<'_, V>,
+        delay: &mut impl DelayNs,
+    ) -> Result<(), Error<SPI::Error>> {
+        bitrates::validate(config.cnf).map_err(Error::InvalidBitTiming)?;
+        self.reset_and_wait(delay).await?;
+        self.write_config(config).await.map_err(Error::Spi)?;
+        self.write_register(config.canctrl)
+            .await
+            .map_err(Error::Spi)?;
+        self.wait_for_mode(config.canctrl.reqop(), delay, APPLY_CONFIG_TIMEOUT_MS)
+            .await
+    }
+
+    /// Everything of [`Config`] except [`CANCTRL`], requires Configuration mode
+    async fn write_config(&mut self, config: &Config<'_, V>) -> Result<(), SPI::Error> {
+        self.bus_off = BusOff::new(config.bus_off_recovery);
+        self.replacing = None;
+        for write in config.register_writes() {
+            self.write_registers(write.address, write.data()).await?;
+        }
+        Ok(())
+    }
+
+    /// See [`crate::MCP25xx::apply_config_verified`]
+    pub async fn apply_config_verified(
+        &mut self,
+        config: &Config<'_, V>,
+        delay: &mut impl DelayNs,
+    ) -> Result<(), VerifyError<SPI::Error>> {
+        self.apply_config(config, delay).await?;
+        let read = self.read_config().await.map_err(Error::Spi)?;
+        let written = read.with_config(config);
+        match read.mismatches(&written).next() {
+            Some(_) => Err(VerifyError::Mismatch { written, read }),
+            None => Ok(()),
+        }
+    }
+
+    /// See [`crate::MCP25xx::read_config`]
+    pub async fn read_config(&mut self) -> Result<ConfigRegisters, SPI::Error> {
+        // Filter0 to Filter2, Filter3 to Filter5, Mask0 to CANINTE
+        let mut buf = [0; CONFIG_REGISTERS_LEN];
+        let (filters, masks) = buf.split_at_mut(6 * AcceptanceFilter::LEN);
+        let (filter0, filter3) = filters.split_at_mut(3 * AcceptanceFilter::LEN);
+        self.read_registers(AcceptanceFilter::Filter0.address(), filter0)
+            .await?;
+        self.read_registers(AcceptanceFilter::Filter3.address(), filter3)
+            .await?;
+        self.read_registers(AcceptanceFilter::Mask0.address(), masks)
+            .await?;
+        Ok(ConfigRegisters::from_bytes(
+            buf,
+            self.read_register().await?,
+            self.read_register().await?,
+            self.read_register().await?,
+        ))
+    }
+
+    /// Set the controller to NormalOperation, Sleep, Loopback, ListenOnly or Configuration
+    pub async fn set_mode(&mut self, mode: OperationMode) -> Result<(), SPI::Error> {
+        let reg = CANCTRL::new().with_reqop(mode);
+        self.modify_register(reg, 0b11100000).await
+    }
+
+    /// See [`crate::MCP25xx::set_mode_and_wait`]
+    pub async fn set_mode_and_wait(
+        &mut self,
+        mode: OperationMode,
+        delay: &mut impl DelayNs,
+        timeout_ms: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.set_mode(mode).await.map_err(Error::Spi)?;
+        self.wait_for_mode(mode, delay, timeout_ms).await
+    }
+
+    async fn wait_for_mode(
+        &mut self,
+        mode: OperationMode,
+        delay: &mut impl DelayNs,
+        timeout_ms: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.poll_mode(mode, delay, polls(timeout_ms)).await
+    }
+
+    async fn poll_mode(
+        &mut self,
+        mode: OperationMode,
+        delay: &mut impl DelayNs,
+        polls: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        let mut initial = None;
+        let mut polled = 0;
+        loop {
+            let canstat: CANSTAT = self.read_register().await.map_err(Error::Spi)?;
+            let opmod = canstat.opmod();
+            if opmod == mode {
+                return Ok(());
+            }
+            if *initial.get_or_insert(opmod) != opmod {
+                return Err(Error::ModeMismatch(opmod));
+            }
+            if polled == polls {
+                return Err(Error::ModeTimeout(opmod));
+            }
+            delay.delay_us(POLL_INTERVAL_US).await;
+            polled += 1;
+        }
+    }
+
+    /// Set clock settings
+    ///
+    /// See [`crate::bitrates`] for preconfigured settings for different oscillator frequencies.
+    ///
+    /// ## Note:
+    /// The controller needs to be in Configuration Mode for this
+    pub async fn set_bitrate(&mut self, cnf: CNF) -> Result<(), SPI::Error> {
+        self.write_registers(cnf.address(), &cnf.into_bytes()).await
+    }
+
+    /// Set individual receive buffer filters or masks
+    ///
+    /// ## Note:
+    /// The controller needs to be in Configuration Mode for this
+    pub async fn set_filter(
+        &mut self,
+        filter: AcceptanceFilter,
+        id: IdHeader,
+    ) -> Result<(), SPI::Error> {
+        self.write_registers(filter.address(), &id.into_bytes())
+            .await
+    }
+
+    /// Set the mask and all filters of a receive buffer
+    ///
+    /// ## Note:
+    /// The controller needs to be in Configuration Mode for this
+    pub async fn set_filter_bank(&mut self, bank: &impl FilterBank) -> Result<(), SPI::Error> {
+        for (filter, id) in bank.registers() {
+            self.set_filter(filter, id).await?;
+        }
+        Ok(())
+    }
+
+    /// Read status flags
+    pub async fn read_status(&mut self) -> Result<ReadStatusResponse, SPI::Error> {
+        let mut buf = [0];
+        self.spi
+            .transaction(&mut [
+                Operation::Write(&[Instruction::ReadStatus as u8]),
+                Operation::Read(&mut buf),
+            ])
+            .await?;
+        Ok(ReadStatusResponse::from_bytes(buf))
+    }
+
+    /// Reset internal registers to the default state. Sets Configuration mode.
+    pub async fn reset(&mut self) -> Result<(), SPI::Error> {
+        self.spi.write(&[Instruction::Reset as u8]).await
+    }
+
+    /// See [`crate::MCP25xx::reset_and_wait`]
+    pub async fn reset_and_wait(
+        &mut self,
+        delay: &mut impl DelayNs,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.reset().await.map_err(Error::Spi)?;
+        delay.delay_us(RESET_DELAY_US).await;
+        let mut regs = [0; 2];
+        self.read_registers(CANSTAT::ADDRESS, &mut regs)
+            .await
+            .map_err(Error::Spi)?;
+        if regs != RESET_VALUES {
+            return Err(Error::DeviceNotFound);
+        }
+        Ok(())
+    }
+
+    /// See [`crate::MCP25xx::error_state`]
+    pub async fn error_state(&mut self) -> Result<ErrorState, SPI::Error> {
+        let mut bytes = [0; ERROR_REGISTERS_LEN];
+        self.read_registers(TEC::ADDRESS, &mut bytes).await?;
+        Ok(ErrorState::from_bytes(bytes))
+    }
+
+    /// See [`crate::MCP25xx::poll_error_state`]
+    pub async fn poll_error_state(&mut self) -> Result<Option<StateTransition>, SPI::Error> {
+        let state = self.error_state().await?;
+        Ok(self.state_tracker.update(state))
+    }
+
+    /// See [`crate::MCP25xx::handle_interrupt`]
+    pub async fn handle_interrupt(&mut self) -> Result<Option<InterruptEvent>, Error<SPI::Error>> {
+        let canstat: CANSTAT = self.read_register().await.map_err(Error::Spi)?;
+        let event = match interrupt::event(canstat.icod()) {
+            Some(InterruptEvent::Error(_)) => {
+                let eflg: EFLG = self.read_register().await.map_err(Error::Spi)?;
+                self.track_bus_off(eflg).await?;
+                let overflows = u8::from(eflg) & OVERFLOW_FLAGS;
+                if overflows != 0 {
+                    self.modify_register(EFLG::new(), overflows)
+                        .await
+                        .map_err(Error::Spi)?;
+                }
+                InterruptEvent::Error(eflg)
+            }
+            Some(event) => event,
+            None => {
+                let intf: CANINTF = self.read_register().await.map_err(Error::Spi)?;
+                if !intf.merrf() {
+                    return Ok(None);
+                }
+                InterruptEvent::MessageError
+            }
+        };
+        if event.flags() != 0 {
+            self.modify_register(CANINTF::new(), event.flags())
+                .await
+                .map_err(Error::Spi)?;
+        }
+        Ok(Some(event))
+    }
+
+    /// See [`crate::MCP25xx::snapshot`]
+    pub async fn snapshot(&mut self) -> Result<RegisterSnapshot, SPI::Error> {
+        let mut bytes = [0; REGISTER_COUNT];
+        // the address space starts with RXF0SIDH
+        self.read_registers(AcceptanceFilter::Filter0.address(), &mut bytes)
+            .await?;
+        Ok(RegisterSnapshot { bytes })
+    }
+
+    /// See [`crate::MCP25xx::recover_from_bus_off`]
+    pub async fn recover_from_bus_off(
+        &mut self,
+        delay: &mut impl DelayNs,
+        timeout_ms: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        if !self.bus_off.held {
+            return Ok(());
+        }
+        delay.delay_ms(self.bus_off.rejoin()).await;
+        self.set_mode_and_wait(OperationMode::NormalOperation, delay, timeout_ms)
+            .await
+    }
+
+    /// See [`crate::MCP25xx::detect_bitrate`]
+    pub async fn detect_bitrate(
+        &mut self,
+        candidates: &[CNF],
+        delay: &mut impl DelayNs,
+        window_ms: u32,
+        timeout_ms: u32,
+    ) -> Result<Option<CNF>, Error<SPI::Error>> {
+        for &cnf in candidates {
+            bitrates::validate(cnf).map_err(Error::InvalidBitTiming)?;
+        }
+        self.set_mode_and_wait(OperationMode::Configuration, delay, timeout_ms)
+            .await?;
+        let rxb0ctrl: RXB0CTRL = self.read_register().await.map_err(Error::Spi)?;
+        let rxb1ctrl: RXB1CTRL = self.read_register().await.map_err(Error::Spi)?;
+        self.write_register(rxb0ctrl.with_rxm(RXM::ReceiveAny))
+            .await
+            .map_err(Error::Spi)?;
+        self.write_register(rxb1ctrl.with_rxm(RXM::ReceiveAny))
+            .await
+            .map_err(Error::Spi)?;
+        let detected = self
+            .try_bitrates(candidates, delay, window_ms, timeout_ms)
+            .await;
+        let restored = match self.write_register(rxb0ctrl).await {
+            Ok(()) => self.write_register(rxb1ctrl).await,
+            Err(e) => Err(e),
+        };
+        let cnf = detected?;
+        restored.map_err(Error::Spi)?;
+        Ok(cnf)
+    }
+
+    async fn try_bitrates(
+        &mut self,
+        candidates: &[CNF],
+        delay: &mut impl DelayNs,
+        window_ms: u32,
+        timeout_ms: u32,
+    ) -> Result<Option<CNF>, Error<SPI::Error>> {
+        self.clear_detection_flags().await.map_err(Error::Spi)?;
+        for &cnf in candidates {
+            self.set_bitrate(cnf).await.map_err(Error::Spi)?;
+            self.set_mode_and_wait(OperationMode::ListenOnly, delay, timeout_ms)
+                .await?;
+            let received = self.listen(delay, window_ms).await.map_err(Error::Spi)?;
+            self.set_mode_and_wait(OperationMode::Configuration, delay, timeout_ms)
+                .await?;
+            self.clear_detection_flags().await.map_err(Error::Spi)?;
+            if received {
+                return Ok(Some(cnf));
+            }
+        }
+        Ok(None)
+    }
+
+    async fn listen(
+        &mut self,
+        delay: &mut impl DelayNs,
+        window_ms: u32,
+    ) -> Result<bool, SPI::Error> {
+        let mut received = false;
+        let mut waited_ms = 0;
+        loop {
+            let intf: CANINTF = self.read_register().await?;
+            if intf.merrf() {
+                return Ok(false);
+            }
+            received |= intf.rx0if() || intf.rx1if();
+            if waited_ms >= window_ms {
+                return Ok(received);
+            }
+            delay.delay_ms(1).await;
+            waited_ms += 1;
+        }
+    }
+
+    async fn clear_detection_flags(&mut self) -> Result<(), SPI::Error> {
+        self.rx_order = RxOrder::default();
+        self.modify_register(CANINTF::new(), BITRATE_DETECTION_FLAGS)
+            .await
+    }
+
+    /// Load the frame into a free transmit buffer and request it to be sent
+    ///
+    /// Same semantics as [`crate::MCP25xx::transmit_tracked`], but returns `None` instead of
+    /// [`nb::Error::WouldBlock`] if all transmit buffers hold frames of higher or equal priority,
+    /// or while a replaced frame is still being transmitted.
+    /// [`InterruptDriven::transmit`] waits for a free transmit buffer instead.
+    pub async fn try_transmit(
+        &mut self,
+        frame: &CanFrame,
+    ) -> Result<Option<Transmission>, Error<SPI::Error>> {
+        if self.bus_off.held {
+            return Err(Error::BusOff);
+        }
+        if let Some(buf_idx) = self.replacing {
+            return self.finish_replacement(buf_idx, frame).await;
+        }
+
+        let status = self.read_status().await.map_err(Error::Spi)?;
+        let mut buf_idx = TxBuffer::TXB0;
+        if status.txreq0() {
+            buf_idx = TxBuffer::TXB1;
+            if status.txreq1() {
+                buf_idx = TxBuffer::TXB2;
+                if status.txreq2() {
+                    return self.replace_pending_frame(frame).await;
+                }
+            }
+        }
+
+        self.load_tx_buffer(buf_idx, frame)
+            .await
+            .map_err(Error::Spi)?;
+        self.request_to_send(buf_idx).await.map_err(Error::Spi)?;
+        self.bus_off.sent();
+        Ok(Some(Transmission {
+            buffer: buf_idx,
+            replaced: None,
+        }))
+    }
+
+    /// See [`crate::MCP25xx::tx_status`]
+    pub async fn tx_status(&mut self, buf_idx: TxBuffer) -> Result<TxStatus, SPI::Error> {
+        let ctrl = self.read_tx_control(buf_idx).await?;
+        if let Some(status) = TxStatus::from_control(ctrl) {
+            return Ok(status);
+        }
+        let intf: CANINTF = self.read_register().await?;
+        Ok(TxStatus::from_flags(ctrl, intf, buf_idx))
+    }
+
+    /// See [`crate::MCP25xx::abort`]
+    pub async fn abort(
+        &mut self,
+        buf_idx: TxBuffer,
+        delay: &mut impl DelayNs,
+        timeout_ms: u32,
+    ) -> Result<Option<CanFrame>, Error<SPI::Error>> {
+        let replacing = self
+            .replacing
+            .take_if(|replacing| *replacing as u8 == buf_idx as u8)
+            .is_some();
+        if !replacing
+            && !self
+                .read_tx_control(buf_idx)
+                .await
+                .map_err(Error::Spi)?
+                .txreq()
+        {
+            return Ok(None);
+        }
+        if self
+            .abort_tx_buffer(buf_idx, delay, polls(timeout_ms))
+            .await?
+            .abtf()
+        {
+            return Ok(Some(
+                self.read_tx_buffer(buf_idx).await.map_err(Error::Spi)?,
+            ));
+        }
+        Ok(None)
+    }
+
+    /// See [`crate::MCP25xx::abort_all`]
+    pub async fn abort_all(
+        &mut self,
+        delay: &mut impl DelayNs,
+        timeout_ms: u32,
+    ) -> Result<[Option<CanFrame>; 3], Error<SPI::Error>> {
+        let mut status = self.read_status().await.map_err(Error::Spi)?;
+        let mut pending = [status.txreq0(), status.txreq1(), status.txreq2()];
+        if let Some(buf_idx) = self.replacing.take() {
+            pending[buf_idx as usize] = true;
+        }
+        self.modify_register(CANCTRL::new().with_abat(true), ABORT_ALL)
+            .await
+            .map_err(Error::Spi)?;
+        let polls = polls(timeout_ms);
+        let mut polled = 0;
+        let mut timed_out = None;
+        while let Some(buf_idx) = pending_tx_buffer(&status) {
+            if polled > polls {
+                timed_out = Some(buf_idx);
+                break;
+            }
+            if polled > 0 {
+                delay.delay_us(POLL_INTERVAL_US).await;
+            }
+            status = self.read_status().await.map_err(Error::Spi)?;
+            polled += 1;
+        }
+        self.modify_register(CANCTRL::new(), ABORT_ALL)
+            .await
+            .map_err(Error::Spi)?;
+        if let Some(buf_idx) = timed_out {
+            return Err(Error::AbortTimeout(buf_idx));
+        }
+
+        let mut frames = [None, None, None];
+        for (buf_idx, txreq) in TxBuffer::ALL.into_iter().zip(pending) {
+            if txreq
+                && self
+                    .read_tx_control(buf_idx)
+                    .await
+                    .map_err(Error::Spi)?
+                    .abtf()
+            {
+                frames[buf_idx as usize] =
+                    Some(self.read_tx_buffer(buf_idx).await.map_err(Error::Spi)?);
+            }
+        }
+        Ok(frames)
+    }
+
+    /// Abort the lowest priority pending frame if it has a lower priority than `frame`
+    /// and load `frame` into its transmit buffer instead
+    async fn replace_pending_frame(
+        &mut self,
+        frame: &CanFrame,
+    ) -> Result<Option<Transmission>, Error<SPI::Error>> {
+        let mut lowest: Option<(TxBuffer, Id)> = None;
+        let mut failed = None;
+        for buf_idx in TxBuffer::ALL {
+            let (ctrl, id) = self.read_tx_header(buf_idx).await.map_err(Error::Spi)?;
+            if ctrl.txerr() && failed.is_none() {
+                failed = Some(buf_idx);
+            }
+            if lowest.is_none_or(|(_, lowest)| id > lowest) {
+                lowest = Some((buf_idx, id));
+            }
+        }
+        if failed.is_some() {
+            let eflg: EFLG = self.read_register().await.map_err(Error::Spi)?;
+            self.track_bus_off(eflg).await?;
+            if eflg.txbo() {
+                return Err(Error::BusOff);
+            }
+        }
+        let (buf_idx, id) = lowest.unwrap();
+        if frame.id() >= id {
+            return match failed {
+                Some(buf_idx) => Err(Error::Transmit(buf_idx)),
+                None => Ok(None),
+            };
+        }
+
+        self.bit_modify(TXBnCTRL(buf_idx).address(), TXREQ, 0)
+            .await
+            .map_err(Error::Spi)?;
+        self.replacing = Some(buf_idx);
+        self.finish_replacement(buf_idx, frame).await
+    }
+
+    /// Load `frame` into the transmit buffer of the aborted frame once it is no longer pending
+    async fn finish_replacement(
+        &mut self,
+        buf_idx: TxBuffer,
+        frame: &CanFrame,
+    ) -> Result<Option<Transmission>, Error<SPI::Error>> {
+        let ctrl = self.read_tx_control(buf_idx).await.map_err(Error::Spi)?;
+        if ctrl.txreq() {
+            return Ok(None);
+        }
+        self.replacing = None;
+        let replaced = if ctrl.abtf() {
+            Some(self.read_tx_buffer(buf_idx).await.map_err(Error::Spi)?)
+        } else {
+            None
+        };
+
+        self.load_tx_buffer(buf_idx, frame)
+            .await
+            .map_err(Error::Spi)?;
+        self.request_to_send(buf_idx).await.map_err(Error::Spi)?;
+        self.bus_off.sent();
+        Ok(Some(Transmission {
+            buffer: buf_idx,
+            replaced,
+        }))
+    }
+
+    /// Apply the [`crate::BusOffRecovery`] policy once `txbo` is set, record its clearing otherwise
+    async fn track_bus_off(&mut self, eflg: EFLG) -> Result<(), Error<SPI::Error>> {
+        if !eflg.txbo() {
+            self.bus_off.recovered();
+            return Ok(());
+        }
+        if self.bus_off.active {
+            return Ok(());
+        }
+        self.enter_bus_off().await
+    }
+
+    /// Abort all pending frames and hold the controller off the bus if the recovery policy says so
+    async fn enter_bus_off(&mut self) -> Result<(), Error<SPI::Error>> {
+        let status = self.read_status().await.map_err(Error::Spi)?;
+        let pending = [status.txreq0(), status.txreq1(), status.txreq2()];
+        for (buf_idx, txreq) in TxBuffer::ALL.into_iter().zip(pending) {
+            if txreq {
+                self.abort_tx_buffer(buf_idx, &mut NoDelay, ABORT_POLLS)
+                    .await?;
+            }
+        }
+        if self.bus_off.enter() {
+            self.set_mode(OperationMode::Configuration)
+                .await
+                .map_err(Error::Spi)?;
+            self.poll_mode(
+                OperationMode::Configuration,
+                &mut NoDelay,
+                BUS_OFF_MODE_POLLS,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Clear `txreq` of the selected transmit buffer and wait until it is no longer pending
+    ///
+    /// TXBnCTRL is read at most `polls` more times with [`POLL_INTERVAL_US`] in between.
+    /// `abtf` of the returned control register tells whether the frame was aborted or sent.
+    async fn abort_tx_buffer(
+        &mut self,
+        buf_idx: TxBuffer,
+        delay: &mut impl DelayNs,
+        polls: u32,
+    ) -> Result<TXB0CTRL, Error<SPI::Error>> {
+        self.bit_modify(TXBnCTRL(buf_idx).address(), TXREQ, 0)
+            .await
+            .map_err(Error::Spi)?;
+        let mut polled = 0;
+        loop {
+            // a frame which is currently being transmitted can not be aborted anymore
+            let ctrl = self.read_tx_control(buf_idx).await.map_err(Error::Spi)?;
+            if !ctrl.txreq() {
+                return Ok(ctrl);
+            }
+            if polled == polls {
+                return Err(Error::AbortTimeout(buf_idx));
+            }
+            delay.delay_us(POLL_INTERVAL_US).await;
+            polled += 1;
+        }
+    }
+
+    /// Read the control register and the CAN ID of the selected transmit buffer
+    async fn read_tx_header(&mut self, buf_idx: TxBuffer) -> Result<(TXB0CTRL, Id), SPI::Error> {
+        let mut bytes = [0; 5];
+        self.read_registers(TXBnCTRL(buf_idx).address(), &mut bytes)
+            .await?;
+        let [ctrl, sidh, sidl, eid8, eid0] = bytes;
+        let id = IdHeader::from_bytes([sidh, sidl, eid8, eid0]).id();
+        Ok((TXB0CTRL::from_bytes([ctrl]), id))
+    }
+
+    /// All TXBnCTRL registers share the layout of [`TXB0CTRL`]
+    async fn read_tx_control(&mut self, buf_idx: TxBuffer) -> Result<TXB0CTRL, SPI::Error> {
+        let mut ctrl = [0];
+        self.read_registers(TXBnCTRL(buf_idx).address(), &mut ctrl)
+            .await?;
+        Ok(TXB0CTRL::from_bytes(ctrl))
+    }
+
+    /// Read the oldest received frame
+    ///
+    /// Same semantics as [`embedded_can::nb::Can::receive`] of [`crate::MCP25xx`],
+    /// but returns `None` instead of [`nb::Error::WouldBlock`] if no frame was received.
+    /// [`InterruptDriven::receive`] waits for the next frame instead.
+    pub async fn try_receive(&mut self) -> Result<Option<CanFrame>, Error<SPI::Error>> {
+        let intf: CANINTF = self.read_register().await.map_err(Error::Spi)?;
+        if let Some(buf_idx) = self.rx_order.next(intf.rx0if(), intf.rx1if()) {
+            let frame = self.read_rx_buffer(buf_idx).await.map_err(Error::Spi)?;
+            if matches!(buf_idx, RxBuffer::RXB0) && !intf.rx1if() {
+                let intf: CANINTF = self.read_register().await.map_err(Error::Spi)?;
+                self.rx_order.rxb0_freed(intf.rx1if());
+            }
+            return Ok(Some(frame));
+        }
+        if intf.errif() {
+            let eflg: EFLG = self.read_register().await.map_err(Error::Spi)?;
+            self.track_bus_off(eflg).await?;
+            if let Some((buf_idx, flag)) = overflowed_rx_buffer(eflg) {
+                self.modify_register(EFLG::new(), flag)
+                    .await
+                    .map_err(Error::Spi)?;
+                self.modify_register(CANINTF::new(), ERROR_FLAG)
+                    .await
+                    .map_err(Error::Spi)?;
+                return Err(Error::Overrun(buf_idx));
+            }
+        }
+        if intf.merrf() {
+            self.modify_register(CANINTF::new(), MESSAGE_ERROR_FLAG)
+                .await
+                .map_err(Error::Spi)?;
+            return Err(Error::MessageError);
+        }
+        if self.bus_off.held {
+            return Err(Error::BusOff);
+        }
+        Ok(None)
+    }
+}
+
+impl<SPI: SpiDevice, V: Mcp2515Features> MCP25xx<SPI, V> {
+    /// Read receive buffer status flags
+    ///
+    /// Returns [`Error::Unsupported`] if a [`Variant::MCP2510`] was selected at runtime.
+    pub async fn rx_status(&mut self) -> Result<RxStatusResponse, Error<SPI::Error>> {
+        if !self.variant.has_buffer_instructions() {
+            return Err(Error::Unsupported);
+        }
+        let mut buf = [0];
+        self.spi
+            .transaction(&mut [
+                Operation::Write(&[Instruction::RxStatus as u8]),
+                Operation::Read(&mut buf),
+            ])
+            .await
+            .map_err(Error::Spi)?;
+        Ok(RxStatusResponse::from_bytes(buf))
+    }
+
+    /// Set a filter or mask comparing standard identifiers and the first two data bytes
+    ///
+    /// ## Note:
+    /// The controller needs to be in Configuration Mode for this
+    pub async fn set_data_byte_filter(
+        &mut self,
+        filter: AcceptanceFilter,
+        id: StandardId,
+        bytes: [u8; 2],
+    ) -> Result<(), SPI::Error> {
+        self.set_filter(filter, IdHeader::with_two_data_bytes(id, bytes))
+            .await
+    }
+}
+
+impl<SPI: SpiDevice, V: ChipVariant> MCP25xx<SPI, V> {
+    /// Read a single register
+    pub async fn read_register<R: Register>(&mut self) -> Result<R, SPI::Error> {
+        let mut reg = [0];
+        self.spi
+            .transaction(&mut [
+                Operation::Write(&[Instruction::Read as u8, R::ADDRESS]),
+                Operation::Read(&mut reg),
+            ])
+            .await?;
+        Ok(reg[0].into())
+    }
+
+    /// Write a single register
+    pub async fn write_register<R: Register + Into<u8>>(
+        &mut self,
+        reg: R,
+    ) -> Result<(), SPI::Error> {
+        self.spi
+            .write(&[Instruction::Write as u8, R::ADDRESS, reg.into()])
+            .await
+    }
+
+    /// Modify a single register
+    ///
+    /// Only registers implementing [`Modify`] support the `Modify` Instruction
+    pub async fn modify_register<R: Register + Modify + Into<u8>>(
+        &mut self,
+        reg: R,
+        mask: u8,
+    ) -> Result<(), SPI::Error> {
+        self.bit_modify(R::ADDRESS, mask, reg.into()).await
+    }
+
+    async fn bit_modify(&mut self, address: u8, mask: u8, data: u8) -> Result<(), SPI::Error> {
+        self.spi
+            .write(&[Instruction::BitModify as u8, address, mask, data])
+            .await
+    }
+
+    /// Read multiple consecutive registers
+    pub async fn read_registers(
+        &mut self,
+        start_address: u8,
+        buf: &mut [u8],
+    ) -> Result<(), SPI::Error> {
+        self.spi
+            .transaction(&mut [
+                Operation::Write(&[Instruction::Read as u8, start_address]),
+                Operation::Read(buf),
+            ])
+            .await
+    }
+
+    /// Write multiple consecutive registers
+    pub async fn write_registers(
+        &mut self,
+        start_address: u8,
+        data: &[u8],
+    ) -> Result<(), SPI::Error> {
+        self.spi
+            .transaction(&mut [
+                Operation::Write(&[Instruction::Write as u8, start_address]),
+                Operation::Write(data),
+            ])
+            .await
+    }
+
+    /// Request the selected transmit buffer to send a CAN frame
+    pub async fn request_to_send(&mut self, buf_idx: TxBuffer) -> Result<(), SPI::Error> {
+        self.spi
+            .write(&[Instruction::Rts as u8 | (1 << buf_idx as u8)])
+            .await
+    }
+
+    /// Set up the selected transmit buffer with CAN frame data
+    pub async fn load_tx_buffer(
+        &mut self,
+        buf_idx: TxBuffer,
+        frame: &CanFrame,
+    ) -> Result<(), SPI::Error> {
+        let data = &frame.as_bytes()[0..5 + frame.dlc()];
+        if !self.variant.has_buffer_instructions() {
+            return self
+                .write_registers(TXBnFRAME(buf_idx).address(), data)
+                .await;
+        }
+
+        self.spi
+            .transaction(&mut [
+                Operation::Write(&[Instruction::LoadTxBuffer as u8 | (buf_idx as u8 * 2)]),
+                Operation::Write(data),
+            ])
+            .await
+    }
+
+    /// Read CAN frame data back from the selected transmit buffer
+    pub async fn read_tx_buffer(&mut self, buf_idx: TxBuffer) -> Result<CanFrame, SPI::Error> {
+        let mut bytes = [0; TXBnFRAME::LEN];
+        self.read_registers(TXBnFRAME(buf_idx).address(), &mut bytes)
+            .await?;
+        Ok(CanFrame::from_bytes(bytes))
+    }
+
+    /// Read CAN frame data from the selected receive buffer
+    pub async fn read_rx_buffer(&mut self, buf_idx: RxBuffer) -> Result<CanFrame, SPI::Error> {
+        let mut bytes = [0; RXBnFRAME::LEN];
+        self.read_rx(buf_idx, &mut bytes).await?;
+        let frame = CanFrame::from_bytes(bytes);
+
+        if !self.variant.has_buffer_instructions() {
+            // need to manually reset the interrupt flag bit if Instruction::ReadRxBuffer is not available
+            self.modify_register(CANINTF::new(), 1 << buf_idx as u8)
+                .await?;
+        }
+        Ok(frame)
+    }
+
+    async fn read_rx(
+        &mut self,
+        buf_idx: RxBuffer,
+        bytes: &mut [u8; RXBnFRAME::LEN],
+    ) -> Result<(), SPI::Error> {
+        if !self.variant.has_buffer_instructions() {
+            return self
+                .read_registers(RXBnFRAME(buf_idx).address(), bytes)
+                .await;
+        }
+
+        self.spi
+            .transaction(&mut [
+                Operation::Write(&[Instruction::ReadRxBuffer as u8 | (buf_idx as u8 * 2)]),
+                Operation::Read(bytes),
+            ])
+            .await
+    }
+}
+
+/// [`MCP25xx`] which also owns the INT pin of the CAN controller
+///
+/// Async counterpart of [`crate::InterruptDriven`].
+/// [`InterruptDriven::receive`] and [`InterruptDriven::transmit`] wait for the INT pin to go low
+/// before talking to the controller again.
+///
+/// ## Note:
+/// Interrupts need to be enabled in the [`CANINTE`] register, at least `rx0ie` and `rx1ie`
+/// for `receive` and `tx0ie` to `tx2ie` for `transmit`.
+pub struct InterruptDriven<SPI: SpiDevice, INT: Wait, V: ChipVariant = Variant> {
+    pub mcp25xx: MCP25xx<SPI, V>,
+    pub int: INT,
+}
+
+impl<SPI: SpiDevice, INT: Wait, V: ChipVariant> InterruptDriven<SPI, INT, V> {
+    /// Wait for the next received frame, servicing all other interrupt sources on the way
+    pub async fn receive(&mut self) -> Result<CanFrame, InterruptError<SPI::Error, INT::Error>> {
+        loop {
+            self.int.wait_for_low().await.map_err(InterruptError::Pin)?;
+            let canstat: CANSTAT = self
+                .mcp25xx
+                .read_register()
+                .await
+                .map_err(|e| InterruptError::Driver(Error::Spi(e)))?;
+            let service = service(canstat.icod());
+            let flags = match service {
+                Service::ClearFlags(flags) => flags,
+                // `icod` always prefers RXB0, which might not hold the older frame.
+                // Overflows and message errors get reported by `receive` as well.
+                _ => match self.mcp25xx.try_receive().await {
+                    Ok(Some(frame)) => return Ok(frame),
+                    Err(e) => return Err(InterruptError::Driver(e)),
+                    Ok(None) => match service {
+                        Service::Error => CANINTF::new().with_errif(true),
+                        _ => continue,
+                    },
+                },
+            };
+            self.mcp25xx
+                .modify_register(CANINTF::new(), flags.into())
+                .await
+                .map_err(|e| InterruptError::Driver(Error::Spi(e)))?;
+        }
+    }
+
+    /// Wait for a transmit buffer to become free and load the frame into it,
+    /// see [`MCP25xx::try_transmit`]
+    ///
+    /// Only `TxComplete` and `WakeUp` interrupts are cleared while waiting,
+    /// received frames and errors are left to [`InterruptDriven::receive`].
+    /// As long as those keep the INT pin low, or a replaced frame is still being transmitted,
+    /// the transmit buffers are polled without waiting.
+    pub async fn transmit(
+        &mut self,
+        frame: &CanFrame,
+    ) -> Result<Transmission, InterruptError<SPI::Error, INT::Error>> {
+        loop {
+            if let Some(transmission) = self
+                .mcp25xx
+                .try_transmit(frame)
+                .await
+                .map_err(InterruptError::Driver)?
+            {
+                return Ok(transmission);
+            }
+            if self.mcp25xx.replacing.is_some() {
+                continue;
+            }
+            self.int.wait_for_low().await.map_err(InterruptError::Pin)?;
+            let canstat: CANSTAT = self
+                .mcp25xx
+                .read_register()
+                .await
+                .map_err(|e| InterruptError::Driver(Error::Spi(e)))?;
+            if let Service::ClearFlags(flags) = service(canstat.icod()) {
+                self.mcp25xx
+                    .modify_register(CANINTF::new(), flags.into())
+                    .await
+                    .map_err(|e| InterruptError::Driver(Error::Spi(e)))?;
+            }
+        }
+    }
+}
@@ -1,4 +1,311 @@
-// values taken from https://github.com/autowp/arduino-mcp2515/blob/master/mcp2515.h
+use crate::registers::CNF;
+
+/// Sample point used for the tables of the 10, 12, 24 and 25 MHz oscillators, in per mille of the bit time
+pub const DEFAULT_SAMPLE_POINT: u16 = 875;
+
+/// Bit timing found by [`calculate`]
+#[derive(Copy, Clone, Debug)]
+pub struct BitTiming {
+    pub cnf: CNF,
+    /// Deviation of the resulting bitrate from the requested one in parts per million
+    pub error_ppm: u32,
+    /// Resulting sample point in per mille of the bit time
+    pub sample_point: u16,
+}
+
+/// No bit timing could be calculated
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BitTimingError {
+    /// The synchronization jump width needs to be between 1 and 4 time quanta
+    InvalidSjw,
+    /// No combination of BRP, PRSEG, PHSEG1 and PHSEG2 reaches the bitrate
+    NoSolution,
+}
+
+/// Calculate the CNF registers for a bitrate in bit/s with an oscillator of `clock_hz`
+///
+/// `sample_point` is given in per mille of the bit time and `sjw` in time quanta.
+/// Picks the timing with the smallest bitrate error, then the one closest to the sample point.
+/// The result always passes [`validate`].
+///
+/// ```
+/// use mcp25xx::bitrates::{calculate, BitTimingError, DEFAULT_SAMPLE_POINT};
+///
+/// const TIMING: mcp25xx::bitrates::BitTiming = match calculate(16_000_000, 500_000, DEFAULT_SAMPLE_POINT, 1) {
+///     Ok(timing) => timing,
+///     Err(_) => panic!("no bit timing for 500 kbit/s"),
+/// };
+/// assert_eq!(TIMING.error_ppm, 0);
+///
+/// let too_fast = calculate(8_000_000, 1_000_000, DEFAULT_SAMPLE_POINT, 1);
+/// assert_eq!(too_fast.unwrap_err(), BitTimingError::NoSolution);
+/// ```
+pub const fn calculate(
+    clock_hz: u32,
+    bitrate: u32,
+    sample_point: u16,
+    sjw: u8,
+) -> Result<BitTiming, BitTimingError> {
+    if sjw < 1 || sjw > 4 {
+        return Err(BitTimingError::InvalidSjw);
+    }
+    if bitrate == 0 {
+        return Err(BitTimingError::NoSolution);
+    }
+    let mut best: Option<BitTiming> = None;
+    let mut brp = 1;
+    while brp <= MAX_BRP {
+        let divisor = 2 * brp * bitrate as u64;
+        let tq = (clock_hz as u64 + divisor / 2) / divisor;
+        if tq >= MIN_TQ
+            && tq <= MAX_TQ
+            && let Some(timing) = segments(clock_hz, bitrate, brp, tq, sample_point, sjw)
+        {
+            best = match best {
+                Some(best)
+                    if best.error_ppm < timing.error_ppm
+                        || (best.error_ppm == timing.error_ppm
+                            && best.sample_point.abs_diff(sample_point)
+                                <= timing.sample_point.abs_diff(sample_point)) =>
+                {
+                    Some(best)
+                }
+                _ => Some(timing),
+            };
+        }
+        brp += 1;
+    }
+    match best {
+        Some(timing) => Ok(timing),
+        None => Err(BitTimingError::NoSolution),
+    }
+}
+
+/// Like [`calculate`] but only returns the CNF registers
+///
+/// Panics if there is no bit timing, which fails to compile when used for a constant:
+///
+/// ```compile_fail
+/// use mcp25xx::bitrates::{cnf, DEFAULT_SAMPLE_POINT};
+/// use mcp25xx::registers::CNF;
+///
+/// const CNF_1000K_BPS: CNF = cnf(8_000_000, 1_000_000, DEFAULT_SAMPLE_POINT, 1);
+/// ```
+pub const fn cnf(clock_hz: u32, bitrate: u32, sample_point: u16, sjw: u8) -> CNF {
+    match calculate(clock_hz, bitrate, sample_point, sjw) {
+        Ok(timing) => timing.cnf,
+        Err(BitTimingError::InvalidSjw) => panic!("SJW needs to be between 1 and 4"),
+        Err(BitTimingError::NoSolution) => panic!("no bit timing for this bitrate"),
+    }
+}
+
+/// Number of distinct BRP values, the time quantum is `2 * (BRP + 1) / clock`
+const MAX_BRP: u64 = 64;
+/// Time quanta per bit required by the CAN specification
+const MIN_TQ: u64 = 8;
+const MAX_TQ: u64 = 25;
+
+/// Split `tq` time quanta into the bit segments
+const fn segments(
+    clock_hz: u32,
+    bitrate: u32,
+    brp: u64,
+    tq: u64,
+    sample_point: u16,
+    sjw: u8,
+) -> Option<BitTiming> {
+    let sjw = sjw as u64;
+    // time quanta up to the sample point: SyncSeg + PropSeg + PS1
+    let sample = (tq * sample_point as u64 + 500) / 1000;
+    let mut ps2 = tq.saturating_sub(sample);
+    if ps2 <= sjw {
+        ps2 = sjw + 1;
+    }
+    if ps2 < 2 {
+        ps2 = 2;
+    }
+    // PropSeg and PS1 have at most 8 time quanta each
+    if ps2 + 17 < tq {
+        ps2 = tq - 17;
+    }
+    // PropSeg + PS1 >= PS2 and >= 2
+    if ps2 > 8 || 1 + 2 * ps2 > tq {
+        return None;
+    }
+    let tseg1 = tq - 1 - ps2;
+    let ps1 = tseg1 / 2;
+    let prop = tseg1 - ps1;
+
+    let bit_clocks = 2 * brp * tq * bitrate as u64;
+    let error_ppm = (clock_hz as u64).abs_diff(bit_clocks) * 1_000_000 / bit_clocks;
+    let cnf = CNF::from_bytes([
+        (ps2 - 1) as u8,
+        BTLMODE | ((ps1 - 1) as u8) << 3 | (prop - 1) as u8,
+        ((sjw - 1) as u8) << 6 | (brp - 1) as u8,
+    ]);
+    if validate(cnf).is_err() {
+        return None;
+    }
+    Some(BitTiming {
+        cnf,
+        error_ppm: error_ppm as u32,
+        sample_point: ((1 + tseg1) * 1000 / tq) as u16,
+    })
+}
+
+/// Properties of CNF registers, see [`analyze`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TimingAnalysis {
+    /// Bitrate in bit/s
+    pub bitrate: u32,
+    /// Length of a time quantum in nanoseconds
+    pub time_quantum_ns: u32,
+    /// Time quanta per bit
+    pub time_quanta: u8,
+    /// Sample point in per mille of the bit time
+    pub sample_point: u16,
+    /// Synchronization jump width in time quanta
+    pub sjw: u8,
+    /// Deviation of the oscillator frequency from its nominal value the bit timing tolerates,
+    /// in parts per million
+    pub oscillator_tolerance_ppm: u32,
+}
+
+/// Rule of the datasheet the CNF registers break, see [`validate`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TimingViolation {
+    /// A bit needs between 8 and 25 time quanta, contains the number of time quanta
+    TimeQuanta(u8),
+    /// PS2 needs at least 2 time quanta, PHSEG2 has to be at least 1
+    PhaseSegment2TooShort,
+    /// PS2 needs to be longer than SJW
+    SjwTooLong,
+    /// PropSeg + PS1 needs to be at least as long as PS2
+    PhaseSegment1TooShort,
+}
+
+/// Check the CNF registers against the bit timing requirements of the datasheet
+///
+/// * 8 to 25 time quanta per bit
+/// * PS2 >= 2
+/// * PS2 > SJW
+/// * PropSeg + PS1 >= PS2, which also covers PropSeg + PS1 >= 2, the propagation delay
+///
+/// Without `btlmode` in [`CNF2`](crate::registers::CNF2), PS2 is the greater of PS1 and
+/// the information processing time of 2 time quanta, PHSEG2 is ignored then.
+pub const fn validate(cnf: CNF) -> Result<(), TimingViolation> {
+    let segments = Segments::from_cnf(cnf);
+    let time_quanta = segments.time_quanta();
+    if time_quanta < MIN_TQ as u8 || time_quanta > MAX_TQ as u8 {
+        return Err(TimingViolation::TimeQuanta(time_quanta));
+    }
+    if segments.ps2 < 2 {
+        return Err(TimingViolation::PhaseSegment2TooShort);
+    }
+    if segments.ps2 <= segments.sjw {
+        return Err(TimingViolation::SjwTooLong);
+    }
+    if segments.prop + segments.ps1 < segments.ps2 {
+        return Err(TimingViolation::PhaseSegment1TooShort);
+    }
+    Ok(())
+}
+
+/// Analyze the bit timing of CNF registers with an oscillator of `clock_hz`
+///
+/// The oscillator tolerance is the lower of both conditions of the CAN specification:
+/// * `min(PS1, PS2) / (2 * (13 * bit time - PS2))`
+/// * `SJW / (20 * bit time)`
+///
+/// ```
+/// use mcp25xx::bitrates::analyze;
+/// use mcp25xx::bitrates::clock_16mhz::CNF_500K_BPS;
+///
+/// let analysis = analyze(16_000_000, CNF_500K_BPS).unwrap();
+/// assert_eq!(analysis.bitrate, 500_000);
+/// assert_eq!(analysis.time_quanta, 16);
+/// assert_eq!(analysis.time_quantum_ns, 125);
+/// ```
+pub const fn analyze(clock_hz: u32, cnf: CNF) -> Result<TimingAnalysis, TimingViolation> {
+    if let Err(violation) = validate(cnf) {
+        return Err(violation);
+    }
+    let segments = Segments::from_cnf(cnf);
+    let time_quanta = segments.time_quanta() as u64;
+    let tq_clocks = 2 * segments.brp as u64;
+    let bit_clocks = tq_clocks * time_quanta;
+    let clock_hz = clock_hz as u64;
+
+    let phase = if segments.ps1 < segments.ps2 {
+        segments.ps1
+    } else {
+        segments.ps2
+    } as u64;
+    let drift_phase = phase * 1_000_000 / (2 * (13 * time_quanta - segments.ps2 as u64));
+    let drift_sjw = segments.sjw as u64 * 1_000_000 / (20 * time_quanta);
+    let drift = if drift_phase < drift_sjw {
+        drift_phase
+    } else {
+        drift_sjw
+    };
+
+    Ok(TimingAnalysis {
+        bitrate: ((clock_hz + bit_clocks / 2) / bit_clocks) as u32,
+        time_quantum_ns: ((tq_clocks * 1_000_000_000 + clock_hz / 2) / clock_hz) as u32,
+        time_quanta: time_quanta as u8,
+        sample_point: ((time_quanta - segments.ps2 as u64) * 1000 / time_quanta) as u16,
+        sjw: segments.sjw,
+        oscillator_tolerance_ppm: drift as u32,
+    })
+}
+
+/// `btlmode` in [`CNF2`](crate::registers::CNF2)
+const BTLMODE: u8 = 0b1000_0000;
+
+/// Segment lengths in time quanta
+struct Segments {
+    /// BRP + 1
+    brp: u8,
+    sjw: u8,
+    prop: u8,
+    ps1: u8,
+    ps2: u8,
+}
+
+impl Segments {
+    const fn from_cnf(cnf: CNF) -> Self {
+        let [cnf3, cnf2, cnf1] = cnf.into_bytes();
+        let ps1 = (cnf2 >> 3 & 0b111) + 1;
+        let ps2 = if cnf2 & BTLMODE != 0 {
+            (cnf3 & 0b111) + 1
+        } else if ps1 > 2 {
+            ps1
+        } else {
+            2
+        };
+        Segments {
+            brp: (cnf1 & 0b11_1111) + 1,
+            sjw: (cnf1 >> 6) + 1,
+            prop: (cnf2 & 0b111) + 1,
+            ps1,
+            ps2,
+        }
+    }
+
+    const fn time_quanta(&self) -> u8 {
+        1 + self.prop + self.ps1 + self.ps2
+    }
+}
+
+/// [`cnf`] with the [`DEFAULT_SAMPLE_POINT`] and a SJW of 1, fails to compile unless the bitrate is exact
+const fn exact(clock_hz: u32, bitrate: u32) -> CNF {
+    match calculate(clock_hz, bitrate, DEFAULT_SAMPLE_POINT, 1) {
+        Ok(timing) if timing.error_ppm == 0 => timing.cnf,
+        _ => panic!("no exact bit timing for this bitrate"),
+    }
+}
+
+// values for 8, 16 and 20 MHz taken from https://github.com/autowp/arduino-mcp2515/blob/master/mcp2515.h
 // cnf2.btlmode = true
 // cnf3.wakfil = false
 // cnf2.sam = ?
@@ -7,7 +314,7 @@
 pub mod clock_8mhz {
     use crate::registers::CNF;
 
-    // pub const CNF_1000K_BPS: CNF = CNF::from_bytes([0x80, 0x80, 0x00]); invalid see https://github.com/autowp/arduino-mcp2515/issues/44
+    // 1000 kbit/s is not possible, a bit would only have 4 time quanta
     pub const CNF_500K_BPS: CNF = CNF::from_bytes([0x82, 0x90, 0x00]);
     pub const CNF_250K_BPS: CNF = CNF::from_bytes([0x85, 0xB1, 0x00]);
     pub const CNF_200K_BPS: CNF = CNF::from_bytes([0x86, 0xB4, 0x00]);
@@ -59,3 +366,80 @@ pub mod clock_20mhz {
     pub const CNF_40K_BPS: CNF = CNF::from_bytes([0x87, 0xFF, 0x09]);
     pub const CNF_33K3_BPS: CNF = CNF::from_bytes([0x87, 0xFF, 0x0B]);
 }
+
+/// Preconfigured CNF registers for 10 Mhz oscillators, calculated with [`calculate`]
+pub mod clock_10mhz {
+    use super::exact;
+    use crate::registers::CNF;
+
+    const CLOCK: u32 = 10_000_000;
+
+    // 1000 kbit/s is not possible, a bit would only have 5 time quanta
+    pub const CNF_500K_BPS: CNF = exact(CLOCK, 500_000);
+    pub const CNF_250K_BPS: CNF = exact(CLOCK, 250_000);
+    pub const CNF_200K_BPS: CNF = exact(CLOCK, 200_000);
+    pub const CNF_125K_BPS: CNF = exact(CLOCK, 125_000);
+    pub const CNF_100K_BPS: CNF = exact(CLOCK, 100_000);
+    pub const CNF_50K_BPS: CNF = exact(CLOCK, 50_000);
+    pub const CNF_40K_BPS: CNF = exact(CLOCK, 40_000);
+    pub const CNF_20K_BPS: CNF = exact(CLOCK, 20_000);
+    pub const CNF_10K_BPS: CNF = exact(CLOCK, 10_000);
+    pub const CNF_5K_BPS: CNF = exact(CLOCK, 5_000);
+}
+
+/// Preconfigured CNF registers for 12 Mhz oscillators, calculated with [`calculate`]
+pub mod clock_12mhz {
+    use super::exact;
+    use crate::registers::CNF;
+
+    const CLOCK: u32 = 12_000_000;
+
+    // 1000 kbit/s is not possible, a bit would only have 6 time quanta
+    pub const CNF_500K_BPS: CNF = exact(CLOCK, 500_000);
+    pub const CNF_250K_BPS: CNF = exact(CLOCK, 250_000);
+    pub const CNF_200K_BPS: CNF = exact(CLOCK, 200_000);
+    pub const CNF_125K_BPS: CNF = exact(CLOCK, 125_000);
+    pub const CNF_100K_BPS: CNF = exact(CLOCK, 100_000);
+    pub const CNF_80K_BPS: CNF = exact(CLOCK, 80_000);
+    pub const CNF_50K_BPS: CNF = exact(CLOCK, 50_000);
+    pub const CNF_40K_BPS: CNF = exact(CLOCK, 40_000);
+    pub const CNF_20K_BPS: CNF = exact(CLOCK, 20_000);
+    pub const CNF_10K_BPS: CNF = exact(CLOCK, 10_000);
+    pub const CNF_5K_BPS: CNF = exact(CLOCK, 5_000);
+}
+
+/// Preconfigured CNF registers for 24 Mhz oscillators, calculated with [`calculate`]
+pub mod clock_24mhz {
+    use super::exact;
+    use crate::registers::CNF;
+
+    const CLOCK: u32 = 24_000_000;
+
+    pub const CNF_1000K_BPS: CNF = exact(CLOCK, 1_000_000);
+    pub const CNF_500K_BPS: CNF = exact(CLOCK, 500_000);
+    pub const CNF_250K_BPS: CNF = exact(CLOCK, 250_000);
+    pub const CNF_200K_BPS: CNF = exact(CLOCK, 200_000);
+    pub const CNF_125K_BPS: CNF = exact(CLOCK, 125_000);
+    pub const CNF_100K_BPS: CNF = exact(CLOCK, 100_000);
+    pub const CNF_80K_BPS: CNF = exact(CLOCK, 80_000);
+    pub const CNF_50K_BPS: CNF = exact(CLOCK, 50_000);
+    pub const CNF_40K_BPS: CNF = exact(CLOCK, 40_000);
+    pub const CNF_20K_BPS: CNF = exact(CLOCK, 20_000);
+    pub const CNF_10K_BPS: CNF = exact(CLOCK, 10_000);
+}
+
+/// Preconfigured CNF registers for 25 Mhz oscillators, calculated with [`calculate`]
+pub mod clock_25mhz {
+    use super::exact;
+    use crate::registers::CNF;
+
+    const CLOCK: u32 = 25_000_000;
+
+    pub const CNF_500K_BPS: CNF = exact(CLOCK, 500_000);
+    pub const CNF_250K_BPS: CNF = exact(CLOCK, 250_000);
+    pub const CNF_125K_BPS: CNF = exact(CLOCK, 125_000);
+    pub const CNF_100K_BPS: CNF = exact(CLOCK, 100_000);
+    pub const CNF_50K_BPS: CNF = exact(CLOCK, 50_000);
+    pub const CNF_20K_BPS: CNF = exact(CLOCK, 20_000);
+    pub const CNF_10K_BPS: CNF = exact(CLOCK, 10_000);
+}
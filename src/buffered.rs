@@ -0,0 +1,154 @@
+use embedded_hal::spi::SpiDevice;
+
+use crate::variant::{ChipVariant, Variant};
+use crate::{CanFrame, Error, MCP25xx};
+
+/// [`MCP25xx`] with a software receive queue holding up to `N` frames
+///
+/// [`BufferedReceiver::poll`] moves received frames from the controller into the queue.
+/// It is meant to be called from the interrupt handler of the INT pin, while the main context
+/// drains the queue with [`BufferedReceiver::pop`] or [`embedded_can::nb::Can::receive`].
+/// Sharing the receiver between both contexts is up to the user,
+/// e.g. with a `critical_section::Mutex<RefCell<_>>`.
+///
+/// ```
+/// # use mcp25xx::doctesthelper::get_mcp25xx;
+/// use embedded_can::nb::Can;
+/// use mcp25xx::BufferedReceiver;
+///
+/// let mut receiver: BufferedReceiver<_, 32> = BufferedReceiver::new(get_mcp25xx());
+///
+/// // interrupt handler
+/// receiver.poll().unwrap();
+///
+/// // main context
+/// while let Some(frame) = receiver.pop() {
+///     // ...
+/// }
+/// ```
+pub struct BufferedReceiver<SPI: SpiDevice, const N: usize, V: ChipVariant = Variant> {
+    pub mcp25xx: MCP25xx<SPI, V>,
+    queue: RingBuffer<N>,
+    dropped: u32,
+    overflows: u32,
+}
+
+impl<SPI: SpiDevice, const N: usize, V: ChipVariant> BufferedReceiver<SPI, N, V> {
+    pub fn new(mcp25xx: MCP25xx<SPI, V>) -> Self {
+        BufferedReceiver {
+            mcp25xx,
+            queue: RingBuffer::new(),
+            dropped: 0,
+            overflows: 0,
+        }
+    }
+
+    /// Move all received frames from the controller into the queue
+    ///
+    /// Frames which do not fit into the queue anymore are dropped.
+    /// Receive buffer overflows ([`Error::Overrun`]) are counted instead of reported.
+    ///
+    /// Returns the number of frames read from the controller.
+    pub fn poll(&mut self) -> Result<usize, Error<SPI::Error>> {
+        let mut received = 0;
+        loop {
+            match embedded_can::nb::Can::receive(&mut self.mcp25xx) {
+                Ok(frame) => {
+                    received += 1;
+                    if !self.queue.push(frame) {
+                        self.dropped = self.dropped.wrapping_add(1);
+                    }
+                }
+                Err(nb::Error::Other(Error::Overrun(_))) => {
+                    self.overflows = self.overflows.wrapping_add(1);
+                }
+                Err(nb::Error::Other(e)) => return Err(e),
+                Err(nb::Error::WouldBlock) => return Ok(received),
+            }
+        }
+    }
+
+    /// Take the oldest frame out of the queue
+    pub fn pop(&mut self) -> Option<CanFrame> {
+        self.queue.pop()
+    }
+
+    /// Number of frames in the queue
+    pub fn len(&self) -> usize {
+        self.queue.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.len == 0
+    }
+
+    /// Number of frames dropped because the queue was full
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+
+    /// Number of frames lost inside the controller because a receive buffer overflowed
+    ///
+    /// ## Note:
+    /// The controller only flags that a receive buffer overflowed,
+    /// several frames lost between two polls are counted once.
+    pub fn overflows(&self) -> u32 {
+        self.overflows
+    }
+}
+
+impl<SPI: SpiDevice, const N: usize, V: ChipVariant> embedded_can::nb::Can
+    for BufferedReceiver<SPI, N, V>
+{
+    type Frame = CanFrame;
+    type Error = Error<SPI::Error>;
+
+    fn transmit(&mut self, frame: &Self::Frame) -> nb::Result<Option<Self::Frame>, Self::Error> {
+        embedded_can::nb::Can::transmit(&mut self.mcp25xx, frame)
+    }
+
+    /// Take the oldest frame out of the queue, polls the controller if the queue is empty
+    fn receive(&mut self) -> nb::Result<Self::Frame, Self::Error> {
+        if self.queue.len == 0 {
+            self.poll()?;
+        }
+        self.pop().ok_or(nb::Error::WouldBlock)
+    }
+}
+
+/// Fixed capacity FIFO queue
+struct RingBuffer<const N: usize> {
+    frames: [CanFrame; N],
+    head: usize,
+    len: usize,
+}
+
+impl<const N: usize> RingBuffer<N> {
+    fn new() -> Self {
+        RingBuffer {
+            frames: core::array::from_fn(|_| CanFrame::default()),
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Returns `false` if the queue is full
+    fn push(&mut self, frame: CanFrame) -> bool {
+        if self.len == N {
+            return false;
+        }
+        self.frames[(self.head + self.len) % N] = frame;
+        self.len += 1;
+        true
+    }
+
+    fn pop(&mut self) -> Option<CanFrame> {
+        if self.len == 0 {
+            return None;
+        }
+        let frame = core::mem::take(&mut self.frames[self.head]);
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+        Some(frame)
+    }
+}
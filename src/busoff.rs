@@ -0,0 +1,97 @@
+/// What the driver does once the controller went bus-off (`txbo` in the [`EFLG`](crate::registers::EFLG) register)
+///
+/// `txbo` is checked by `handle_interrupt` on error interrupts, by `receive` while `errif`
+/// in [`CANINTF`](crate::registers::CANINTF) is set and by `transmit` once a pending frame failed.
+/// Pending frames are aborted in any case and [`Error::BusOff`](crate::Error::BusOff)
+/// is returned by `transmit` and `receive` while the controller is held off the bus.
+/// Policies holding the controller off the bus switch it to Configuration mode,
+/// [`MCP25xx::recover_from_bus_off`](crate::MCP25xx::recover_from_bus_off)
+/// switches it back to NormalOperation.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum BusOffRecovery {
+    /// Let the controller rejoin the bus on its own after 128 occurrences of 11 recessive bits
+    #[default]
+    Automatic,
+    /// Hold the controller off the bus, `recover_from_bus_off` waits this many milliseconds before rejoining
+    Delayed(u32),
+    /// Hold the controller off the bus until `recover_from_bus_off` is called
+    Manual,
+    /// Recover automatically this many times, then behave like [`BusOffRecovery::Manual`]
+    ///
+    /// The count starts over once 256 frames were sent without the controller going bus-off again.
+    Attempts(u8),
+}
+
+/// Frames sent after a bus-off until [`BusOffRecovery::Attempts`] starts counting again
+const ERROR_FREE_FRAMES: u16 = 256;
+
+/// Bus-off bookkeeping of the driver
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct BusOff {
+    pub(crate) policy: BusOffRecovery,
+    attempts: u8,
+    /// Frames sent since the last bus-off
+    sent: u16,
+    /// `txbo` was seen and has not been cleared since
+    pub(crate) active: bool,
+    /// The controller is held in Configuration mode
+    pub(crate) held: bool,
+}
+
+impl BusOff {
+    pub(crate) fn new(policy: BusOffRecovery) -> Self {
+        BusOff {
+            policy,
+            ..Default::default()
+        }
+    }
+
+    /// Record `txbo` being set, returns `true` if the controller needs to be held off the bus
+    pub(crate) fn enter(&mut self) -> bool {
+        if self.active {
+            return false;
+        }
+        self.active = true;
+        self.sent = 0;
+        self.held = match self.policy {
+            BusOffRecovery::Automatic => false,
+            BusOffRecovery::Delayed(_) | BusOffRecovery::Manual => true,
+            BusOffRecovery::Attempts(max) if self.attempts < max => {
+                self.attempts += 1;
+                false
+            }
+            BusOffRecovery::Attempts(_) => true,
+        };
+        self.held
+    }
+
+    /// Record a sent frame, or a frame loaded into a free transmit buffer whose former frame left
+    pub(crate) fn sent(&mut self) {
+        if self.active {
+            return;
+        }
+        self.sent = self.sent.saturating_add(1);
+        if self.sent >= ERROR_FREE_FRAMES {
+            self.attempts = 0;
+        }
+    }
+
+    /// Record `txbo` being cleared by the controller
+    pub(crate) fn recovered(&mut self) {
+        self.active = false;
+    }
+
+    /// Release the controller, returns the milliseconds to wait before rejoining the bus
+    pub(crate) fn rejoin(&mut self) -> u32 {
+        let delay_ms = match self.policy {
+            BusOffRecovery::Delayed(ms) => ms,
+            _ => 0,
+        };
+        // `txbo` may stay set until the controller finished its own recovery sequence
+        *self = BusOff {
+            active: true,
+            ..BusOff::new(self.policy)
+        };
+        delay_ms
+    }
+}
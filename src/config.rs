@@ -1,22 +1,62 @@
-use crate::registers::{CANCTRL, CNF, OperationMode, RXB0CTRL, RXB1CTRL};
-use crate::{AcceptanceFilter, IdHeader};
+use core::marker::PhantomData;
+
+use embedded_can::StandardId;
+
+use crate::filters::{Filter, FilterBank, Mask, Rxb0Filters, Rxb1Filters};
+use crate::registers::{
+    CANCTRL, CANINTE, CNF, CNF1, CNF2, CNF3, OperationMode, RXB0CTRL, RXB1CTRL, RXM, Register,
+    RegisterBlock,
+};
+use crate::variant::{FrameFormat, Mcp2510Features, Mcp2515Features, Variant};
+use crate::{AcceptanceFilter, BusOffRecovery, IdHeader, RxBuffer};
 
 /// Configuration for:
 /// * Clock settings
 /// * Operation Mode
 /// * Receive buffers
 /// * Receive buffer filters and masks
+/// * Interrupt enables
 /// * Other flags inside the CANCTRL, CNF, RXB0CTRL, RXB1CTRL registers
-#[derive(Clone, Debug, Default)]
-pub struct Config<'a> {
+/// * Bus-off recovery policy of the driver
+///
+/// Settings only some chips support are only available for the matching [`crate::variant`].
+#[derive(Clone, Debug)]
+pub struct Config<'a, V = Variant> {
     pub canctrl: CANCTRL,
     pub cnf: CNF,
     pub rxb0ctrl: RXB0CTRL,
     pub rxb1ctrl: RXB1CTRL,
+    pub caninte: CANINTE,
     pub filters: &'a [(AcceptanceFilter, IdHeader)],
+    pub rxb0_filters: Option<Rxb0Filters>,
+    pub rxb1_filters: Option<Rxb1Filters>,
+    pub bus_off_recovery: BusOffRecovery,
+    variant: PhantomData<V>,
+}
+
+impl<V> Default for Config<'_, V> {
+    /// Configuration without a bitrate
+    ///
+    /// The CNF registers stay zeroed, which is no valid bit timing as it depends on the oscillator:
+    /// [`crate::MCP25xx::apply_config`] returns [`crate::Error::InvalidBitTiming`] until
+    /// a bitrate is set with [`Config::bitrate`].
+    fn default() -> Self {
+        Config {
+            canctrl: CANCTRL::default(),
+            cnf: CNF::default(),
+            rxb0ctrl: RXB0CTRL::default(),
+            rxb1ctrl: RXB1CTRL::default(),
+            caninte: CANINTE::default(),
+            filters: &[],
+            rxb0_filters: None,
+            rxb1_filters: None,
+            bus_off_recovery: BusOffRecovery::default(),
+            variant: PhantomData,
+        }
+    }
 }
 
-impl<'a> Config<'a> {
+impl<'a, V> Config<'a, V> {
     #[inline]
     pub fn mode(mut self, mode: OperationMode) -> Self {
         self.canctrl.set_reqop(mode);
@@ -42,9 +82,271 @@ impl<'a> Config<'a> {
         self.rxb1ctrl = rxb1ctrl;
         self
     }
+    /// Interrupt sources which pull the INT pin low, see [`crate::MCP25xx::handle_interrupt`]
+    #[inline]
+    pub fn interrupts(mut self, caninte: CANINTE) -> Self {
+        self.caninte = caninte;
+        self
+    }
+    /// Individual filters and masks, written before the filter banks
     #[inline]
     pub fn filters(mut self, filters: &'a [(AcceptanceFilter, IdHeader)]) -> Self {
         self.filters = filters;
         self
     }
+    /// Mask and filters of RXB0
+    #[inline]
+    pub fn receive_buffer_0_filters(mut self, filters: Rxb0Filters) -> Self {
+        self.rxb0_filters = Some(filters);
+        self
+    }
+    /// Mask and filters of RXB1
+    #[inline]
+    pub fn receive_buffer_1_filters(mut self, filters: Rxb1Filters) -> Self {
+        self.rxb1_filters = Some(filters);
+        self
+    }
+    #[inline]
+    pub fn bus_off_recovery(mut self, policy: BusOffRecovery) -> Self {
+        self.bus_off_recovery = policy;
+        self
+    }
+}
+
+impl<V: Mcp2515Features> Config<'_, V> {
+    /// Only attempt to transmit each frame once
+    #[inline]
+    pub fn one_shot_mode(mut self, enabled: bool) -> Self {
+        self.canctrl.set_osm(enabled);
+        self
+    }
+    /// Output the start-of-frame signal on the CLKOUT pin instead of the clock
+    #[inline]
+    pub fn start_of_frame_signal(mut self, enabled: bool) -> Self {
+        self.cnf.cnf3.set_sof(enabled);
+        self
+    }
+    /// Compare standard identifiers and the first two data bytes in a mask or filter
+    /// of the filter banks
+    ///
+    /// Replaces the register in the bank of [`Config::receive_buffer_0_filters`] or
+    /// [`Config::receive_buffer_1_filters`], a bank which was not set starts zeroed.
+    pub fn data_byte_filter(
+        mut self,
+        filter: AcceptanceFilter,
+        id: StandardId,
+        bytes: [u8; 2],
+    ) -> Self {
+        let (mask, data_filter) = (
+            Mask::with_two_data_bytes(id, bytes),
+            Filter::with_two_data_bytes(id, bytes),
+        );
+        let rxb0 = &mut self.rxb0_filters;
+        let rxb1 = &mut self.rxb1_filters;
+        match filter {
+            AcceptanceFilter::Filter0 => rxb0.get_or_insert_default().filters[0] = data_filter,
+            AcceptanceFilter::Filter1 => rxb0.get_or_insert_default().filters[1] = data_filter,
+            AcceptanceFilter::Filter2 => rxb1.get_or_insert_default().filters[0] = data_filter,
+            AcceptanceFilter::Filter3 => rxb1.get_or_insert_default().filters[1] = data_filter,
+            AcceptanceFilter::Filter4 => rxb1.get_or_insert_default().filters[2] = data_filter,
+            AcceptanceFilter::Filter5 => rxb1.get_or_insert_default().filters[3] = data_filter,
+            AcceptanceFilter::Mask0 => rxb0.get_or_insert_default().mask = mask,
+            AcceptanceFilter::Mask1 => rxb1.get_or_insert_default().mask = mask,
+        }
+        self
+    }
+}
+
+impl<V: Mcp2510Features> Config<'_, V> {
+    /// Only receive frames of the given format meeting the filter criteria into the receive buffer
+    #[inline]
+    pub fn receive_only(mut self, buf_idx: RxBuffer, format: FrameFormat) -> Self {
+        let rxm = match format {
+            FrameFormat::Standard => RXM::Reserved1,
+            FrameFormat::Extended => RXM::Reserved2,
+        };
+        match buf_idx {
+            RxBuffer::RXB0 => self.rxb0ctrl.set_rxm(rxm),
+            RxBuffer::RXB1 => self.rxb1ctrl.set_rxm(rxm),
+        }
+        self
+    }
+}
+
+impl<V> Config<'_, V> {
+    /// Registers written by `apply_config` before CANCTRL, shared by the blocking and async driver
+    ///
+    /// CNF, RXB0CTRL and RXB1CTRL come first, followed by the individual filters,
+    /// the filter banks and CANINTE.
+    pub(crate) fn register_writes(&self) -> impl Iterator<Item = RegisterWrite> + '_ {
+        let filters = (self.filters.iter().copied())
+            .chain(self.rxb0_filters.iter().flat_map(FilterBank::registers))
+            .chain(self.rxb1_filters.iter().flat_map(FilterBank::registers))
+            .map(|(filter, id)| RegisterWrite::new(filter.address(), &id.into_bytes()));
+        [
+            RegisterWrite::new(self.cnf.address(), &self.cnf.into_bytes()),
+            RegisterWrite::new(RXB0CTRL::ADDRESS, &[self.rxb0ctrl.into()]),
+            RegisterWrite::new(RXB1CTRL::ADDRESS, &[self.rxb1ctrl.into()]),
+        ]
+        .into_iter()
+        .chain(filters)
+        .chain([RegisterWrite::new(CANINTE::ADDRESS, &[self.caninte.into()])])
+    }
+}
+
+/// Consecutive registers written by a single `Write` instruction
+#[derive(Copy, Clone)]
+pub(crate) struct RegisterWrite {
+    pub(crate) address: u8,
+    bytes: [u8; 4],
+    len: usize,
+}
+
+impl RegisterWrite {
+    fn new(address: u8, data: &[u8]) -> Self {
+        let mut bytes = [0; 4];
+        bytes[..data.len()].copy_from_slice(data);
+        RegisterWrite {
+            address,
+            bytes,
+            len: data.len(),
+        }
+    }
+
+    pub(crate) fn data(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+}
+
+/// Filter and mask registers followed by CNF3 to CNF1 and CANINTE
+pub(crate) const CONFIG_REGISTERS_LEN: usize = 8 * AcceptanceFilter::LEN + CNF::LEN + 1;
+
+/// Configuration registers read back from the controller, see [`crate::MCP25xx::read_config`]
+///
+/// Read-only bits like the filter hits in RXB0CTRL and RXB1CTRL hold their current state.
+#[derive(Copy, Clone, Debug)]
+pub struct ConfigRegisters {
+    pub canctrl: CANCTRL,
+    pub cnf: CNF,
+    pub rxb0ctrl: RXB0CTRL,
+    pub rxb1ctrl: RXB1CTRL,
+    pub caninte: CANINTE,
+    /// `Filter0` to `Filter5`, `Mask0` and `Mask1`
+    pub filters: [(AcceptanceFilter, IdHeader); 8],
+}
+
+/// Register holding another value than written
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RegisterMismatch {
+    pub address: u8,
+    pub written: u8,
+    pub read: u8,
+}
+
+impl ConfigRegisters {
+    /// `bytes` from the registers of `Filter0` to `Filter2`, `Filter3` to `Filter5`
+    /// and `Mask0` to `CANINTE`
+    pub(crate) fn from_bytes(
+        bytes: [u8; CONFIG_REGISTERS_LEN],
+        canctrl: CANCTRL,
+        rxb0ctrl: RXB0CTRL,
+        rxb1ctrl: RXB1CTRL,
+    ) -> Self {
+        let id = |at: usize| {
+            IdHeader::from_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+        };
+        ConfigRegisters {
+            canctrl,
+            cnf: CNF::from_bytes([bytes[32], bytes[33], bytes[34]]),
+            rxb0ctrl,
+            rxb1ctrl,
+            caninte: bytes[35].into(),
+            filters: [
+                (AcceptanceFilter::Filter0, id(0)),
+                (AcceptanceFilter::Filter1, id(4)),
+                (AcceptanceFilter::Filter2, id(8)),
+                (AcceptanceFilter::Filter3, id(12)),
+                (AcceptanceFilter::Filter4, id(16)),
+                (AcceptanceFilter::Filter5, id(20)),
+                (AcceptanceFilter::Mask0, id(24)),
+                (AcceptanceFilter::Mask1, id(28)),
+            ],
+        }
+    }
+
+    /// [`Config`] writing the same registers
+    pub fn as_config<V>(&self) -> Config<'_, V> {
+        Config {
+            canctrl: self.canctrl,
+            cnf: self.cnf,
+            rxb0ctrl: self.rxb0ctrl,
+            rxb1ctrl: self.rxb1ctrl,
+            caninte: self.caninte,
+            filters: &self.filters,
+            rxb0_filters: None,
+            rxb1_filters: None,
+            bus_off_recovery: BusOffRecovery::default(),
+            variant: PhantomData,
+        }
+    }
+
+    /// Registers whose writable bits differ from the `written` ones
+    pub fn mismatches<'a>(
+        &'a self,
+        written: &'a ConfigRegisters,
+    ) -> impl Iterator<Item = RegisterMismatch> + 'a {
+        self.registers()
+            .zip(written.registers())
+            .filter(|&((_, read, writable), (_, written, _))| (read ^ written) & writable != 0)
+            .map(|((address, read, _), (_, written, _))| RegisterMismatch {
+                address,
+                written,
+                read,
+            })
+    }
+
+    /// The registers after writing `config`, registers it does not write keep their value
+    pub(crate) fn with_config<V>(mut self, config: &Config<'_, V>) -> Self {
+        self.canctrl = config.canctrl;
+        self.cnf = config.cnf;
+        self.rxb0ctrl = config.rxb0ctrl;
+        self.rxb1ctrl = config.rxb1ctrl;
+        self.caninte = config.caninte;
+
+        let banks = (config.rxb0_filters.iter().flat_map(FilterBank::registers))
+            .chain(config.rxb1_filters.iter().flat_map(FilterBank::registers));
+        for (filter, id) in config.filters.iter().copied().chain(banks) {
+            for entry in &mut self.filters {
+                if entry.0.address() == filter.address() {
+                    entry.1 = id;
+                }
+            }
+        }
+        self
+    }
+
+    /// Address, value and writable bits of every register
+    fn registers(&self) -> impl Iterator<Item = (u8, u8, u8)> + '_ {
+        let [cnf3, cnf2, cnf1] = self.cnf.into_bytes();
+        let registers = [
+            (CANCTRL::ADDRESS, self.canctrl.into(), 0xFF),
+            (CNF3::ADDRESS, cnf3, 0b1100_0111),
+            (CNF2::ADDRESS, cnf2, 0xFF),
+            (CNF1::ADDRESS, cnf1, 0xFF),
+            // rxm and bukt
+            (RXB0CTRL::ADDRESS, self.rxb0ctrl.into(), 0b0110_0100),
+            (RXB1CTRL::ADDRESS, self.rxb1ctrl.into(), 0b0110_0000),
+            (CANINTE::ADDRESS, self.caninte.into(), 0xFF),
+        ];
+        let filters = self.filters.iter().flat_map(|&(filter, id)| {
+            // EXIDE is unimplemented in the mask registers
+            let sidl = match filter {
+                AcceptanceFilter::Mask0 | AcceptanceFilter::Mask1 => 0b1110_0011,
+                _ => 0b1110_1011,
+            };
+            let writable = [0xFF, sidl, 0xFF, 0xFF];
+            (0..4).map(move |n| (filter.address() + n as u8, id.into_bytes()[n], writable[n]))
+        });
+        registers.into_iter().chain(filters)
+    }
 }
@@ -1,20 +1,155 @@
-use crate::MCP25xx;
+use crate::{Instruction, MCP25xx};
 use core::convert::Infallible;
+use embedded_hal::digital::InputPin;
 use embedded_hal::spi::{ErrorType, Operation, SpiDevice};
 
 /// used for doc tests
-pub fn get_mcp25xx() -> MCP25xx<NoOpSPI> {
-    MCP25xx { spi: NoOpSPI }
+pub fn get_mcp25xx() -> MCP25xx<FakeSPI> {
+    MCP25xx::new(FakeSPI::default())
 }
 
-pub struct NoOpSPI;
+/// Register file answering like an idle controller
+#[derive(Default)]
+pub struct FakeSPI {
+    registers: Registers,
+}
 
-impl SpiDevice for NoOpSPI {
-    fn transaction(&mut self, _: &mut [Operation<'_, u8>]) -> Result<(), Self::Error> {
+impl SpiDevice for FakeSPI {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Self::Error> {
+        self.registers.transaction(operations);
         Ok(())
     }
 }
 
-impl ErrorType for NoOpSPI {
+impl ErrorType for FakeSPI {
+    type Error = Infallible;
+}
+
+struct Registers {
+    bytes: [u8; 128],
+}
+
+impl Default for Registers {
+    fn default() -> Self {
+        let mut bytes = [0; 128];
+        bytes[CANSTAT_ADDRESS] = 0b1000_0000;
+        bytes[CANCTRL_ADDRESS] = 0b1000_0111;
+        Registers { bytes }
+    }
+}
+
+const CANSTAT_ADDRESS: usize = 0x0E;
+const CANCTRL_ADDRESS: usize = 0x0F;
+
+enum State {
+    Instruction,
+    Address(u8),
+    Read(usize),
+    Write(usize),
+    Mask(usize),
+    Modify(usize, u8),
+    Ignore,
+}
+
+impl Registers {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) {
+        let mut state = State::Instruction;
+        for operation in operations {
+            match operation {
+                Operation::Write(bytes) => {
+                    for &byte in bytes.iter() {
+                        state = self.write(state, byte);
+                    }
+                }
+                Operation::Read(bytes) => {
+                    for byte in bytes.iter_mut() {
+                        *byte = 0;
+                        if let State::Read(address) = state {
+                            *byte = self.bytes[address % 128];
+                            state = State::Read(address + 1);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn write(&mut self, state: State, byte: u8) -> State {
+        match state {
+            State::Instruction if byte == Instruction::Reset as u8 => {
+                *self = Registers::default();
+                State::Ignore
+            }
+            State::Instruction => State::Address(byte),
+            State::Address(instruction) => {
+                let address = byte as usize;
+                match instruction {
+                    i if i == Instruction::Read as u8 => State::Read(address),
+                    i if i == Instruction::Write as u8 => State::Write(address),
+                    i if i == Instruction::BitModify as u8 => State::Mask(address),
+                    _ => State::Ignore,
+                }
+            }
+            State::Write(address) => {
+                self.set(address % 128, byte);
+                State::Write(address + 1)
+            }
+            State::Mask(address) => State::Modify(address, byte),
+            State::Modify(address, mask) => {
+                let byte = self.bytes[address] & !mask | byte & mask;
+                self.set(address, byte);
+                State::Ignore
+            }
+            state => state,
+        }
+    }
+
+    /// Mode changes take effect immediately
+    fn set(&mut self, address: usize, byte: u8) {
+        self.bytes[address] = byte;
+        if address == CANCTRL_ADDRESS {
+            let opmod = byte & 0b1110_0000;
+            self.bytes[CANSTAT_ADDRESS] = self.bytes[CANSTAT_ADDRESS] & 0b0001_1111 | opmod;
+        }
+    }
+}
+
+/// INT pin which is never asserted
+pub struct NoOpPin;
+
+impl InputPin for NoOpPin {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(true)
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(false)
+    }
+}
+
+impl embedded_hal::digital::ErrorType for NoOpPin {
     type Error = Infallible;
 }
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::spi::SpiDevice for FakeSPI {
+    async fn transaction(
+        &mut self,
+        operations: &mut [Operation<'_, u8>],
+    ) -> Result<(), Self::Error> {
+        self.registers.transaction(operations);
+        Ok(())
+    }
+}
+
+pub struct NoOpDelay;
+
+impl embedded_hal::delay::DelayNs for NoOpDelay {
+    fn delay_ns(&mut self, _: u32) {}
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::delay::DelayNs for NoOpDelay {
+    async fn delay_ns(&mut self, _: u32) {}
+}
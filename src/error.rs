@@ -0,0 +1,81 @@
+use core::fmt::Debug;
+
+use embedded_can::ErrorKind;
+
+use crate::bitrates::TimingViolation;
+use crate::registers::OperationMode;
+use crate::{ConfigRegisters, RxBuffer, TxBuffer};
+
+/// Error of the CAN controller driver
+///
+/// Besides errors of the SPI device, this reports error conditions flagged by the controller.
+///
+/// ## Note:
+/// The controller does not tell which kind of bus error caused `txerr` or `merrf`,
+/// these map to [`ErrorKind::Other`].
+/// Lost arbitration and aborted frames are reported by [`TxStatus`](crate::TxStatus) instead.
+#[derive(Debug)]
+pub enum Error<E> {
+    /// Error of the SPI device
+    Spi(E),
+    /// A frame was lost because the receive buffer was still full
+    /// (`rx0ovr` or `rx1ovr` in the [`EFLG`](crate::registers::EFLG) register)
+    Overrun(RxBuffer),
+    /// The transmit error counter exceeded 255 and the controller went bus-off
+    /// (`txbo` in the [`EFLG`](crate::registers::EFLG) register)
+    BusOff,
+    /// A bus error occurred while transmitting the frame of the transmit buffer
+    /// (`txerr` in the TXBnCTRL register)
+    Transmit(TxBuffer),
+    /// An error occurred while receiving a frame
+    /// (`merrf` in the [`CANINTF`](crate::registers::CANINTF) register)
+    MessageError,
+    /// The chip [`Variant`](crate::Variant) does not support the operation
+    Unsupported,
+    /// No controller responded after a reset
+    DeviceNotFound,
+    /// The controller did not switch to the requested operation mode in time,
+    /// it is still in the contained mode
+    ModeTimeout(OperationMode),
+    /// The controller switched to another operation mode than the requested one
+    ModeMismatch(OperationMode),
+    /// The CNF registers break the bit timing requirements of the datasheet
+    InvalidBitTiming(TimingViolation),
+    /// The transmit buffer was still pending when the timeout of an abort ran out
+    AbortTimeout(TxBuffer),
+}
+
+impl<E: Debug> embedded_can::Error for Error<E> {
+    /// * [`Error::Overrun`] maps to [`ErrorKind::Overrun`]
+    /// * [`Error::BusOff`], [`Error::Transmit`] and [`Error::MessageError`] map to
+    ///   [`ErrorKind::Other`]: the controller has no register telling bit, stuff, CRC, form
+    ///   and acknowledge errors apart. [`EFLG`](crate::registers::EFLG) only reports
+    ///   the error counter thresholds (warning, error-passive, bus-off) and receive overflows.
+    /// * every other variant is no bus error and maps to [`ErrorKind::Other`]
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Overrun(_) => ErrorKind::Overrun,
+            Error::BusOff | Error::Transmit(_) | Error::MessageError => ErrorKind::Other,
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+/// Error of [`MCP25xx::apply_config_verified`](crate::MCP25xx::apply_config_verified)
+#[derive(Debug)]
+pub enum VerifyError<E> {
+    /// Applying or reading back the configuration failed
+    Apply(Error<E>),
+    /// The registers were read back with other values than written,
+    /// see [`ConfigRegisters::mismatches`]
+    Mismatch {
+        written: ConfigRegisters,
+        read: ConfigRegisters,
+    },
+}
+
+impl<E> From<Error<E>> for VerifyError<E> {
+    fn from(error: Error<E>) -> Self {
+        VerifyError::Apply(error)
+    }
+}
@@ -0,0 +1,76 @@
+use crate::registers::{EFLG, REC, Register, TEC};
+
+/// Number of registers from [`TEC`] up to and including [`EFLG`]
+pub(crate) const ERROR_REGISTERS_LEN: usize = (EFLG::ADDRESS - TEC::ADDRESS + 1) as usize;
+
+/// Fault confinement state of the CAN controller
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum BusState {
+    /// Both error counters are below 96
+    #[default]
+    ErrorActive,
+    /// One of the error counters reached 96 (`ewarn` in the [`EFLG`] register)
+    ErrorWarning,
+    /// One of the error counters reached 128 (`txep` or `rxep` in the [`EFLG`] register)
+    ErrorPassive,
+    /// The transmit error counter exceeded 255 (`txbo` in the [`EFLG`] register)
+    BusOff,
+}
+
+impl From<EFLG> for BusState {
+    fn from(eflg: EFLG) -> Self {
+        if eflg.txbo() {
+            BusState::BusOff
+        } else if eflg.txep() || eflg.rxep() {
+            BusState::ErrorPassive
+        } else if eflg.ewarn() {
+            BusState::ErrorWarning
+        } else {
+            BusState::ErrorActive
+        }
+    }
+}
+
+/// Error counters and flags, see [`crate::MCP25xx::error_state`]
+#[derive(Copy, Clone, Debug)]
+pub struct ErrorState {
+    pub bus_state: BusState,
+    pub tec: TEC,
+    pub rec: REC,
+    pub eflg: EFLG,
+}
+
+impl ErrorState {
+    /// Decode the registers from [`TEC`] up to [`EFLG`]
+    pub(crate) fn from_bytes(bytes: [u8; ERROR_REGISTERS_LEN]) -> Self {
+        let eflg = EFLG::from_bytes([bytes[ERROR_REGISTERS_LEN - 1]]);
+        ErrorState {
+            bus_state: eflg.into(),
+            tec: TEC(bytes[0]),
+            rec: REC(bytes[1]),
+            eflg,
+        }
+    }
+}
+
+/// Change of the [`BusState`], see [`crate::MCP25xx::poll_error_state`]
+#[derive(Copy, Clone, Debug)]
+pub struct StateTransition {
+    /// State seen by the previous call
+    pub from: BusState,
+    /// Current state together with the error counters
+    pub to: ErrorState,
+}
+
+/// Remembers the last seen [`BusState`] to detect transitions
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct StateTracker {
+    bus_state: BusState,
+}
+
+impl StateTracker {
+    pub(crate) fn update(&mut self, state: ErrorState) -> Option<StateTransition> {
+        let from = core::mem::replace(&mut self.bus_state, state.bus_state);
+        (from != state.bus_state).then_some(StateTransition { from, to: state })
+    }
+}
@@ -0,0 +1,207 @@
+//! Each receive buffer compares incoming identifiers with its filters under one mask:
+//! RXB0 has [`Mask0`](crate::AcceptanceFilter::Mask0) and two filters,
+//! RXB1 has [`Mask1`](crate::AcceptanceFilter::Mask1) and four filters.
+//! [`Rxb0Filters`](crate::filters::Rxb0Filters) and [`Rxb1Filters`](crate::filters::Rxb1Filters)
+//! always configure a mask together with all filters of its receive buffer.
+//!
+//! [`FilterPlan`](crate::filters::FilterPlan) chooses the masks and filters for a set of wanted
+//! identifiers. It covers the wanted ranges with as few accepted identifiers as possible
+//! and reports the unwanted identifiers which leak through.
+//!
+//! ```
+//! # use mcp25xx::doctesthelper::{FakeSPI, NoOpDelay};
+//! use embedded_can::{ExtendedId, StandardId};
+//! use mcp25xx::bitrates::clock_16mhz::CNF_500K_BPS;
+//! use mcp25xx::filters::{FilterPlan, IdRange};
+//! use mcp25xx::{Config, MCP25xx};
+//!
+//! # let spi = FakeSPI::default();
+//! # let mut delay = NoOpDelay;
+//! let wanted = [
+//!     IdRange::Standard(StandardId::new(0x100).unwrap(), StandardId::new(0x10F).unwrap()),
+//!     IdRange::from(ExtendedId::new(0x18FE_F100).unwrap()),
+//! ];
+//! let plan = FilterPlan::new(&wanted, &[]);
+//! assert_eq!(plan.leaks().count(), 0);
+//!
+//! let config = Config::default()
+//!     .bitrate(CNF_500K_BPS)
+//!     .receive_buffer_0(plan.rxb0ctrl)
+//!     .receive_buffer_0_filters(plan.rxb0)
+//!     .receive_buffer_1_filters(plan.rxb1);
+//!
+//! let mut mcp25xx = MCP25xx::new(spi);
+//! mcp25xx.apply_config(&config, &mut delay).unwrap();
+//! ```
+
+use embedded_can::{ExtendedId, Id, StandardId};
+
+use crate::{AcceptanceFilter, IdHeader};
+
+mod plan;
+
+pub use plan::{FilterPlan, IdRange, Leaks};
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Bits of the identifier a receive buffer compares with its filters
+///
+/// One mask applies to standard and extended frames alike:
+/// * the 11 bits of a standard identifier are compared under the upper 11 bits of the mask
+/// * the 29 bits of an extended identifier are compared under all 29 bits of the mask
+/// * on the MCP2515 and MCP25625, the lower 16 bits of the mask compare the first two data bytes
+///   of standard frames with filters for standard identifiers,
+///   see [`Config::data_byte_filter`](crate::Config::data_byte_filter)
+///
+/// Whether standard or extended frames pass is decided by the [`Filter`].
+/// A buffer with filters for standard identifiers should use [`Mask::standard`],
+/// unless it filters on data bytes.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Mask(IdHeader);
+
+impl Mask {
+    /// Every identifier passes, the filters are ignored
+    pub const ANY: Mask = Mask(IdHeader::from_bytes([0; 4]));
+
+    /// Compare the set bits of standard identifiers and the matching upper 11 bits of extended
+    /// identifiers, ignoring the lower 18 bits of extended identifiers and the data bytes
+    ///
+    /// `Mask::standard(StandardId::MAX)` lets only exact matches pass.
+    pub fn standard(bits: StandardId) -> Self {
+        Mask(bits.into())
+    }
+
+    /// Compare the set bits of extended identifiers
+    ///
+    /// The upper 11 bits apply to standard identifiers, the lower 16 bits to the data bytes
+    /// of standard frames on the MCP2515.
+    /// `Mask::extended(ExtendedId::MAX)` lets only exact matches pass.
+    pub fn extended(bits: ExtendedId) -> Self {
+        Mask(bits.into())
+    }
+
+    /// Compare the set bits of standard identifiers and of the first two data bytes
+    pub(crate) fn with_two_data_bytes(bits: StandardId, bytes: [u8; 2]) -> Self {
+        Mask(IdHeader::with_two_data_bytes(bits, bytes))
+    }
+
+    /// Bits of the mask as extended identifier, the upper 11 bits apply to standard identifiers
+    pub fn bits(&self) -> u32 {
+        let [sidh, sidl, eid8, eid0] = self.0.into_bytes();
+        ((sidh as u32) << 21)
+            | ((sidl as u32 & 0xE0) << 13)
+            | ((sidl as u32 & 0b11) << 16)
+            | ((eid8 as u32) << 8)
+            | eid0 as u32
+    }
+}
+
+/// Identifier a receive buffer accepts under its [`Mask`]
+///
+/// Filters for standard identifiers only let standard frames pass,
+/// filters for extended identifiers only extended frames.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Filter(IdHeader);
+
+impl Filter {
+    /// Accept standard frames with the identifier
+    pub fn standard(id: StandardId) -> Self {
+        Filter(id.into())
+    }
+
+    /// Accept extended frames with the identifier
+    pub fn extended(id: ExtendedId) -> Self {
+        Filter(id.into())
+    }
+
+    /// Accept standard frames with the identifier and the first two data bytes
+    pub(crate) fn with_two_data_bytes(id: StandardId, bytes: [u8; 2]) -> Self {
+        Filter(IdHeader::with_two_data_bytes(id, bytes))
+    }
+
+    /// Identifier of the filter
+    pub fn id(&self) -> Id {
+        self.0.id()
+    }
+}
+
+impl From<StandardId> for Filter {
+    fn from(id: StandardId) -> Self {
+        Filter::standard(id)
+    }
+}
+
+impl From<ExtendedId> for Filter {
+    fn from(id: ExtendedId) -> Self {
+        Filter::extended(id)
+    }
+}
+
+impl From<Id> for Filter {
+    fn from(id: Id) -> Self {
+        Filter(id.into())
+    }
+}
+
+/// Mask and filters of a receive buffer, see [`crate::MCP25xx::set_filter_bank`]
+pub trait FilterBank: sealed::Sealed {
+    /// Registers of the mask and the filters
+    fn registers(&self) -> impl Iterator<Item = (AcceptanceFilter, IdHeader)>;
+}
+
+/// Mask and filters of RXB0
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Rxb0Filters {
+    pub mask: Mask,
+    pub filters: [Filter; 2],
+}
+
+/// Mask and filters of RXB1
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Rxb1Filters {
+    pub mask: Mask,
+    pub filters: [Filter; 4],
+}
+
+impl Rxb0Filters {
+    pub fn new(mask: Mask, filters: [Filter; 2]) -> Self {
+        Rxb0Filters { mask, filters }
+    }
+}
+
+impl Rxb1Filters {
+    pub fn new(mask: Mask, filters: [Filter; 4]) -> Self {
+        Rxb1Filters { mask, filters }
+    }
+}
+
+impl sealed::Sealed for Rxb0Filters {}
+impl sealed::Sealed for Rxb1Filters {}
+
+impl FilterBank for Rxb0Filters {
+    fn registers(&self) -> impl Iterator<Item = (AcceptanceFilter, IdHeader)> {
+        let [f0, f1] = self.filters;
+        [
+            (AcceptanceFilter::Mask0, self.mask.0),
+            (AcceptanceFilter::Filter0, f0.0),
+            (AcceptanceFilter::Filter1, f1.0),
+        ]
+        .into_iter()
+    }
+}
+
+impl FilterBank for Rxb1Filters {
+    fn registers(&self) -> impl Iterator<Item = (AcceptanceFilter, IdHeader)> {
+        let [f2, f3, f4, f5] = self.filters;
+        [
+            (AcceptanceFilter::Mask1, self.mask.0),
+            (AcceptanceFilter::Filter2, f2.0),
+            (AcceptanceFilter::Filter3, f3.0),
+            (AcceptanceFilter::Filter4, f4.0),
+            (AcceptanceFilter::Filter5, f5.0),
+        ]
+        .into_iter()
+    }
+}
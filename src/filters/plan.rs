@@ -0,0 +1,513 @@
+use embedded_can::{ExtendedId, Id, StandardId};
+
+use super::{Filter, Mask, Rxb0Filters, Rxb1Filters};
+use crate::registers::RXB0CTRL;
+
+const STANDARD_MASK: u32 = 0x7FF;
+const EXTENDED_MASK: u32 = 0x1FFF_FFFF;
+/// Bits of an extended identifier behind the 11 bits of the standard identifier
+const EID_BITS: u32 = 18;
+const EID_MASK: u32 = (1 << EID_BITS) - 1;
+
+/// Filters of RXB0 and RXB1
+const SLOTS: [usize; 2] = [2, 4];
+
+/// Inclusive range of standard or extended identifiers, the bounds may be given in either order
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum IdRange {
+    Standard(StandardId, StandardId),
+    Extended(ExtendedId, ExtendedId),
+}
+
+impl IdRange {
+    /// Whether the identifier is part of the range
+    pub fn contains(&self, id: Id) -> bool {
+        let (extended, first, last) = self.bounds();
+        let (id_extended, raw) = raw(id);
+        extended == id_extended && (first..=last).contains(&raw)
+    }
+
+    fn bounds(&self) -> (bool, u32, u32) {
+        let (extended, a, b) = match *self {
+            IdRange::Standard(a, b) => (false, a.as_raw() as u32, b.as_raw() as u32),
+            IdRange::Extended(a, b) => (true, a.as_raw(), b.as_raw()),
+        };
+        (extended, a.min(b), a.max(b))
+    }
+}
+
+impl From<StandardId> for IdRange {
+    fn from(id: StandardId) -> Self {
+        IdRange::Standard(id, id)
+    }
+}
+
+impl From<ExtendedId> for IdRange {
+    fn from(id: ExtendedId) -> Self {
+        IdRange::Extended(id, id)
+    }
+}
+
+impl From<Id> for IdRange {
+    fn from(id: Id) -> Self {
+        match id {
+            Id::Standard(id) => id.into(),
+            Id::Extended(id) => id.into(),
+        }
+    }
+}
+
+/// Masks and filters accepting a set of wanted identifiers
+///
+/// Without high priority identifiers, standard and extended identifiers are preferably
+/// split between the two receive buffers: a buffer accepting standard frames cannot mask
+/// the lower 18 bits of extended identifiers, as the MCP2515 compares them
+/// with the first two data bytes of standard frames.
+///
+/// If nothing is wanted, only the extended identifier `0x1FFFFFFF` is accepted.
+#[derive(Copy, Clone, Debug)]
+pub struct FilterPlan<'a> {
+    /// Mask and filters for [`Config::receive_buffer_0_filters`](crate::Config::receive_buffer_0_filters)
+    pub rxb0: Rxb0Filters,
+    /// Mask and filters for [`Config::receive_buffer_1_filters`](crate::Config::receive_buffer_1_filters)
+    pub rxb1: Rxb1Filters,
+    /// Enables rollover into RXB1 if high priority identifiers were given,
+    /// for [`Config::receive_buffer_0`](crate::Config::receive_buffer_0)
+    pub rxb0ctrl: RXB0CTRL,
+    wanted: &'a [IdRange],
+    priority: &'a [IdRange],
+    /// Filter0 to Filter5 with the mask of their buffer applied
+    accepted: [Pattern; 6],
+}
+
+impl<'a> FilterPlan<'a> {
+    /// Plan masks and filters for the `wanted` and `priority` identifiers
+    ///
+    /// `priority` identifiers are received in RXB0 and roll over into RXB1 if RXB0 is still full,
+    /// RXB1 receives the `wanted` identifiers.
+    /// Without `priority` identifiers, the `wanted` identifiers are spread over both buffers.
+    pub fn new(wanted: &'a [IdRange], priority: &'a [IdRange]) -> Self {
+        let banks = if priority.is_empty() {
+            let mut all = Bank::default();
+            for range in wanted {
+                all.push_range(range, SLOTS[0] + SLOTS[1], own_size);
+            }
+            all.split()
+        } else {
+            let mut banks = [Bank::default(); 2];
+            for range in priority {
+                banks[0].push_range(range, SLOTS[0], shared_size);
+            }
+            for range in wanted {
+                banks[1].push_range(range, SLOTS[1], shared_size);
+            }
+            banks
+        };
+
+        let mut masks = [0; 2];
+        let mut accepted = [FALLBACK; 6];
+        let mut slot = 0;
+        for (idx, bank) in banks.iter().enumerate() {
+            // an unused buffer repeats the other one, so it accepts nothing new
+            let patterns = match (bank.patterns(), banks[1 - idx].patterns()) {
+                ([], []) => &[FALLBACK][..],
+                ([], other) => other,
+                (patterns, _) => patterns,
+            };
+            let (mask, filters) = refine(patterns, SLOTS[idx]);
+            masks[idx] = mask;
+            for n in 0..SLOTS[idx] {
+                accepted[slot] = filters.patterns[n.min(filters.len - 1)];
+                slot += 1;
+            }
+        }
+
+        let mask = |idx: usize| Mask::extended(extended_id(masks[idx]));
+        let filter = |slot: usize| accepted[slot].filter();
+
+        FilterPlan {
+            rxb0: Rxb0Filters::new(mask(0), [filter(0), filter(1)]),
+            rxb1: Rxb1Filters::new(mask(1), [filter(2), filter(3), filter(4), filter(5)]),
+            rxb0ctrl: RXB0CTRL::default().with_bukt(!priority.is_empty()),
+            wanted,
+            priority,
+            accepted,
+        }
+    }
+
+    /// Whether a frame with the identifier passes the filters
+    pub fn accepts(&self, id: Id) -> bool {
+        let (extended, raw) = raw(id);
+        self.accepted.iter().any(|p| p.matches(extended, raw))
+    }
+
+    /// Identifiers which pass the filters without being wanted
+    ///
+    /// The identifiers are computed while iterating,
+    /// a masked extended identifier may let through millions of them.
+    pub fn leaks(&self) -> Leaks<'_, 'a> {
+        Leaks {
+            plan: self,
+            idx: 0,
+            subset: Some(0),
+        }
+    }
+
+    fn is_wanted(&self, id: Id) -> bool {
+        self.wanted
+            .iter()
+            .chain(self.priority)
+            .any(|range| range.contains(id))
+    }
+}
+
+/// Iterator over the unwanted identifiers passing a [`FilterPlan`]
+#[derive(Clone, Debug)]
+pub struct Leaks<'p, 'a> {
+    plan: &'p FilterPlan<'a>,
+    idx: usize,
+    /// Next combination of the bits ignored by the filter at `idx`
+    subset: Option<u32>,
+}
+
+impl Iterator for Leaks<'_, '_> {
+    type Item = Id;
+
+    fn next(&mut self) -> Option<Id> {
+        while let Some(pattern) = self.plan.accepted.get(self.idx) {
+            let earlier = &self.plan.accepted[..self.idx];
+            let subset = match self.subset {
+                Some(subset) if !earlier.contains(pattern) => subset,
+                _ => {
+                    self.idx += 1;
+                    self.subset = Some(0);
+                    continue;
+                }
+            };
+            let free = full(pattern.extended) & !pattern.care;
+            self.subset = (subset != free).then(|| ((subset | !free) + 1) & free);
+
+            let raw = pattern.value | subset;
+            // identifiers passing an earlier filter were already reported
+            if earlier.iter().any(|p| p.matches(pattern.extended, raw)) {
+                continue;
+            }
+            let id = if pattern.extended {
+                Id::Extended(extended_id(raw))
+            } else {
+                Id::Standard(standard_id(raw))
+            };
+            if !self.plan.is_wanted(id) {
+                return Some(id);
+            }
+        }
+        None
+    }
+}
+
+/// Identifiers whose `care` bits equal `value`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Pattern {
+    extended: bool,
+    value: u32,
+    care: u32,
+}
+
+const FALLBACK: Pattern = Pattern {
+    extended: true,
+    value: EXTENDED_MASK,
+    care: EXTENDED_MASK,
+};
+
+impl Pattern {
+    fn matches(self, extended: bool, raw: u32) -> bool {
+        self.extended == extended && raw & self.care == self.value
+    }
+
+    fn covers(self, other: Pattern) -> bool {
+        self.extended == other.extended
+            && other.care & self.care == self.care
+            && other.value & self.care == self.value
+    }
+
+    fn merge(self, other: Pattern) -> Pattern {
+        let care = self.care & other.care & !(self.value ^ other.value);
+        Pattern {
+            extended: self.extended,
+            value: self.value & care,
+            care,
+        }
+    }
+
+    /// Apply the mask of the receive buffer, given as extended identifier
+    fn masked(self, mask: u32) -> Pattern {
+        let care = if self.extended {
+            mask
+        } else {
+            mask >> EID_BITS
+        };
+        Pattern {
+            extended: self.extended,
+            value: self.value & care,
+            care,
+        }
+    }
+
+    /// Number of accepted identifiers
+    fn size(self) -> u64 {
+        1 << (full(self.extended) & !self.care).count_ones()
+    }
+
+    fn filter(self) -> Filter {
+        if self.extended {
+            Filter::extended(extended_id(self.value))
+        } else {
+            Filter::standard(standard_id(self.value))
+        }
+    }
+}
+
+/// Patterns sharing a mask, merged until they fit into the filters of a receive buffer
+#[derive(Copy, Clone, Debug)]
+struct Bank {
+    patterns: [Pattern; 7],
+    len: usize,
+}
+
+impl Default for Bank {
+    fn default() -> Self {
+        Bank {
+            patterns: [FALLBACK; 7],
+            len: 0,
+        }
+    }
+}
+
+impl Bank {
+    fn patterns(&self) -> &[Pattern] {
+        &self.patterns[..self.len]
+    }
+
+    /// Add the aligned blocks making up the range
+    fn push_range(&mut self, range: &IdRange, slots: usize, cost: fn(&[Pattern]) -> u64) {
+        let (extended, mut first, last) = range.bounds();
+        let full = full(extended);
+        loop {
+            // largest aligned block starting at `first` which ends within the range
+            let mut bits = first.trailing_zeros().min(full.count_ones());
+            while first + ((1 << bits) - 1) > last {
+                bits -= 1;
+            }
+            let care = full & !((1 << bits) - 1);
+            self.push(
+                Pattern {
+                    extended,
+                    value: first,
+                    care,
+                },
+                slots,
+                cost,
+            );
+
+            first += 1 << bits;
+            if first > last {
+                break;
+            }
+        }
+    }
+
+    /// Add the pattern and merge the pair of patterns with the lowest `cost`
+    /// if there are more patterns than `slots`
+    fn push(&mut self, pattern: Pattern, slots: usize, cost: fn(&[Pattern]) -> u64) {
+        if self.patterns().iter().any(|p| p.covers(pattern)) {
+            return;
+        }
+        self.add(pattern);
+        if self.len <= slots {
+            return;
+        }
+
+        let mut best: Option<(u64, Bank)> = None;
+        for i in 0..self.len {
+            for j in i + 1..self.len {
+                let (a, b) = (self.patterns[i], self.patterns[j]);
+                // with at least two slots, there is always a pair of the same format
+                if a.extended != b.extended {
+                    continue;
+                }
+                let mut merged = Bank::default();
+                merged.add(a.merge(b));
+                for (k, &p) in self.patterns().iter().enumerate() {
+                    if k != i && k != j && !merged.patterns[0].covers(p) {
+                        merged.add(p);
+                    }
+                }
+                let merged_cost = cost(merged.patterns());
+                if best.is_none_or(|(best_cost, _)| merged_cost < best_cost) {
+                    best = Some((merged_cost, merged));
+                }
+            }
+        }
+        if let Some((_, merged)) = best {
+            *self = merged;
+        }
+    }
+
+    fn add(&mut self, pattern: Pattern) {
+        self.patterns[self.len] = pattern;
+        self.len += 1;
+    }
+
+    /// Distribute the patterns over RXB0 and RXB1 with the fewest accepted identifiers
+    fn split(&self) -> [Bank; 2] {
+        let mut best: Option<(u64, [Bank; 2])> = None;
+        for assignment in 0..1u32 << self.len {
+            let mut banks = [Bank::default(); 2];
+            for (idx, &p) in self.patterns().iter().enumerate() {
+                banks[(assignment >> idx) as usize & 1].add(p);
+            }
+            if banks[0].len > SLOTS[0] || banks[1].len > SLOTS[1] {
+                continue;
+            }
+            let cost = (0..2)
+                .map(|idx| own_size(refine(banks[idx].patterns(), SLOTS[idx]).1.patterns()))
+                .sum();
+            if best.is_none_or(|(best_cost, _)| cost < best_cost) {
+                best = Some((cost, banks));
+            }
+        }
+        best.map_or([Bank::default(); 2], |(_, banks)| banks)
+    }
+}
+
+/// Mask of a receive buffer as extended identifier
+///
+/// Standard frames are compared with the lower 18 bits of the mask on the MCP2515,
+/// these stay clear as soon as the buffer accepts standard identifiers.
+fn common_mask(patterns: &[Pattern]) -> u32 {
+    let mut sid = STANDARD_MASK;
+    let mut eid = EID_MASK;
+    for p in patterns {
+        if p.extended {
+            sid &= p.care >> EID_BITS;
+            eid &= p.care;
+        } else {
+            sid &= p.care;
+            eid = 0;
+        }
+    }
+    (sid << EID_BITS) | eid
+}
+
+/// Narrow the mask of a receive buffer as long as the patterns fit into its filters
+///
+/// A pattern ignoring a bit of the mask takes up one filter for each value of the bit.
+/// Returns the mask and the filters.
+fn refine(patterns: &[Pattern], slots: usize) -> (u32, Bank) {
+    let mut mask = common_mask(patterns);
+    let Some(mut filters) = expand(patterns, mask, slots) else {
+        // every pattern cares about the bits of the common mask and takes up a single filter
+        unreachable!()
+    };
+    let candidates = if patterns.iter().all(|p| p.extended) {
+        EXTENDED_MASK
+    } else {
+        STANDARD_MASK << EID_BITS
+    };
+
+    loop {
+        let mut best: Option<(u32, Bank)> = None;
+        let mut best_cost = own_size(filters.patterns());
+        for bit in (0..29)
+            .map(|n| 1 << n)
+            .filter(|bit| candidates & !mask & bit != 0)
+        {
+            if let Some(narrowed) = expand(patterns, mask | bit, slots) {
+                let cost = own_size(narrowed.patterns());
+                if cost < best_cost {
+                    best_cost = cost;
+                    best = Some((mask | bit, narrowed));
+                }
+            }
+        }
+        match best {
+            Some((narrowed_mask, narrowed)) => {
+                mask = narrowed_mask;
+                filters = narrowed;
+            }
+            None => return (mask, filters),
+        }
+    }
+}
+
+/// Filters for the patterns under the mask, if there are no more than `slots`
+fn expand(patterns: &[Pattern], mask: u32, slots: usize) -> Option<Bank> {
+    let mut filters = Bank::default();
+    for &p in patterns {
+        let masked = p.masked(mask);
+        let extra = masked.care & !p.care;
+        let mut subset = 0;
+        loop {
+            let filter = Pattern {
+                value: masked.value | subset,
+                ..masked
+            };
+            if !filters.patterns().contains(&filter) {
+                if filters.len == slots {
+                    return None;
+                }
+                filters.add(filter);
+            }
+            if subset == extra {
+                break;
+            }
+            subset = ((subset | !extra) + 1) & extra;
+        }
+    }
+    Some(filters)
+}
+
+/// Accepted identifiers of patterns filtered on their own
+fn own_size(patterns: &[Pattern]) -> u64 {
+    patterns.iter().map(|p| p.size()).sum()
+}
+
+/// Accepted identifiers of patterns sharing a mask
+fn shared_size(patterns: &[Pattern]) -> u64 {
+    let mask = common_mask(patterns);
+    let mut size = 0;
+    for (idx, p) in patterns.iter().enumerate() {
+        let p = p.masked(mask);
+        if !patterns[..idx].iter().any(|q| q.masked(mask) == p) {
+            size += p.size();
+        }
+    }
+    size
+}
+
+fn full(extended: bool) -> u32 {
+    if extended {
+        EXTENDED_MASK
+    } else {
+        STANDARD_MASK
+    }
+}
+
+fn raw(id: Id) -> (bool, u32) {
+    match id {
+        Id::Standard(id) => (false, id.as_raw() as u32),
+        Id::Extended(id) => (true, id.as_raw()),
+    }
+}
+
+fn standard_id(raw: u32) -> StandardId {
+    // SAFETY:
+    // masked to 11 bits
+    unsafe { StandardId::new_unchecked(raw as u16 & STANDARD_MASK as u16) }
+}
+
+fn extended_id(raw: u32) -> ExtendedId {
+    // SAFETY:
+    // masked to 29 bits
+    unsafe { ExtendedId::new_unchecked(raw & EXTENDED_MASK) }
+}
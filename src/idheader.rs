@@ -11,9 +11,11 @@ pub struct IdHeader {
 }
 
 impl IdHeader {
-    #[cfg(any(feature = "mcp2515", feature = "mcp25625"))]
-    #[cfg_attr(doc, doc(cfg(any(feature = "mcp2515", feature = "mcp25625"))))]
-    pub fn with_two_data_bytes(id: StandardId, bytes: [u8; 2]) -> Self {
+    /// Filter on the first two data bytes of standard frames
+    ///
+    /// Only reachable through chips with [`crate::variant::Mcp2515Features`],
+    /// the MCP2510 only compares the identifier.
+    pub(crate) fn with_two_data_bytes(id: StandardId, bytes: [u8; 2]) -> Self {
         let id = id.as_raw();
         IdHeader {
             sidh: (id >> 3) as u8,
@@ -46,6 +48,15 @@ impl IdHeader {
         self.sidl & 0b0000_1000 > 0
     }
 
+    pub(crate) const fn from_bytes(bytes: [u8; 4]) -> Self {
+        IdHeader {
+            sidh: bytes[0],
+            sidl: bytes[1],
+            eid8: bytes[2],
+            eid0: bytes[3],
+        }
+    }
+
     pub(crate) fn into_bytes(self) -> [u8; 4] {
         [self.sidh, self.sidl, self.eid8, self.eid0]
     }
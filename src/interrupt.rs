@@ -0,0 +1,180 @@
+use core::fmt::Debug;
+
+use embedded_can::ErrorKind;
+use embedded_hal::digital::InputPin;
+use embedded_hal::spi::SpiDevice;
+
+use crate::registers::*;
+use crate::variant::{ChipVariant, Variant};
+use crate::{CanFrame, ERROR_FLAG, Error, MCP25xx, MESSAGE_ERROR_FLAG, RxBuffer, TxBuffer};
+
+/// [`MCP25xx`] which also owns the INT pin of the CAN controller
+///
+/// [`embedded_can::nb::Can::receive`] only talks to the controller while the INT pin is low.
+/// The interrupt source is then read from [`CANSTAT::icod`] and serviced.
+///
+/// ## Note:
+/// Interrupts need to be enabled in the [`CANINTE`] register, at least `rx0ie` and `rx1ie`,
+/// e.g. with [`Config::interrupts`](crate::Config::interrupts).
+///
+/// ```
+/// # use mcp25xx::doctesthelper::{get_mcp25xx, NoOpPin};
+/// use embedded_can::nb::Can;
+/// use mcp25xx::InterruptDriven;
+/// use mcp25xx::registers::CANINTE;
+///
+/// let mut mcp25xx = get_mcp25xx();
+/// mcp25xx.write_register(CANINTE::new().with_rx0ie(true).with_rx1ie(true)).unwrap();
+///
+/// # let int = NoOpPin;
+/// // int is a struct implementing embedded_hal::digital::InputPin.
+/// let mut mcp25xx = InterruptDriven { mcp25xx, int };
+///
+/// if let Ok(frame) = mcp25xx.receive() {
+///     // ...
+/// }
+/// ```
+pub struct InterruptDriven<SPI: SpiDevice, INT: InputPin, V: ChipVariant = Variant> {
+    pub mcp25xx: MCP25xx<SPI, V>,
+    pub int: INT,
+}
+
+impl<SPI: SpiDevice, INT: InputPin, V: ChipVariant> InterruptDriven<SPI, INT, V> {
+    /// Service pending interrupts until a frame was received or the INT pin goes high
+    pub fn service_interrupts(
+        &mut self,
+    ) -> Result<Option<CanFrame>, InterruptError<SPI::Error, INT::Error>> {
+        while self.int.is_low().map_err(InterruptError::Pin)? {
+            let canstat: CANSTAT = self
+                .mcp25xx
+                .read_register()
+                .map_err(|e| InterruptError::Driver(Error::Spi(e)))?;
+            let service = service(canstat.icod());
+            let flags = match service {
+                Service::ClearFlags(flags) => flags,
+                // `icod` always prefers RXB0, which might not hold the older frame.
+                // Overflows and message errors get reported by `receive` as well.
+                _ => match embedded_can::nb::Can::receive(&mut self.mcp25xx) {
+                    Ok(frame) => return Ok(Some(frame)),
+                    Err(nb::Error::Other(e)) => return Err(InterruptError::Driver(e)),
+                    Err(nb::Error::WouldBlock) => match service {
+                        Service::Error => CANINTF::new().with_errif(true),
+                        Service::Idle => break,
+                        _ => continue,
+                    },
+                },
+            };
+            self.mcp25xx
+                .modify_register(CANINTF::new(), flags.into())
+                .map_err(|e| InterruptError::Driver(Error::Spi(e)))?;
+        }
+        Ok(None)
+    }
+}
+
+/// Interrupt reported by [`MCP25xx::handle_interrupt`]
+#[derive(Copy, Clone, Debug)]
+pub enum InterruptEvent {
+    /// The receive buffer holds a frame, see [`MCP25xx::read_rx_buffer`]
+    RxReady(RxBuffer),
+    /// The frame of the transmit buffer was sent, the buffer is free again
+    TxComplete(TxBuffer),
+    /// The [`EFLG`] register changed
+    Error(EFLG),
+    /// Bus activity while the controller was in Sleep mode
+    WakeUp,
+    /// An error occurred while receiving a frame
+    MessageError,
+}
+
+impl InterruptEvent {
+    /// Flags of the [`CANINTF`] register cleared when handling the event
+    ///
+    /// The flags of the receive buffers are only cleared by reading the buffers,
+    /// the controller must not overwrite the frame before.
+    pub(crate) fn flags(&self) -> u8 {
+        match self {
+            InterruptEvent::RxReady(_) => 0,
+            InterruptEvent::TxComplete(buf_idx) => 1 << (*buf_idx as u8 + 2),
+            InterruptEvent::Error(_) => ERROR_FLAG,
+            InterruptEvent::WakeUp => 0b0100_0000,
+            InterruptEvent::MessageError => MESSAGE_ERROR_FLAG,
+        }
+    }
+}
+
+/// Error of [`InterruptDriven`]
+#[derive(Debug)]
+pub enum InterruptError<S, P> {
+    /// Error of the CAN controller driver
+    Driver(Error<S>),
+    /// Error of the INT pin
+    Pin(P),
+}
+
+impl<S: Debug, P: Debug> embedded_can::Error for InterruptError<S, P> {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            InterruptError::Driver(e) => e.kind(),
+            InterruptError::Pin(_) => ErrorKind::Other,
+        }
+    }
+}
+
+impl<SPI: SpiDevice, INT: InputPin, V: ChipVariant> embedded_can::nb::Can
+    for InterruptDriven<SPI, INT, V>
+{
+    type Frame = CanFrame;
+    type Error = InterruptError<SPI::Error, INT::Error>;
+
+    fn transmit(&mut self, frame: &Self::Frame) -> nb::Result<Option<Self::Frame>, Self::Error> {
+        embedded_can::nb::Can::transmit(&mut self.mcp25xx, frame)
+            .map_err(|e| e.map(InterruptError::Driver))
+    }
+
+    fn receive(&mut self) -> nb::Result<Self::Frame, Self::Error> {
+        self.service_interrupts()?.ok_or(nb::Error::WouldBlock)
+    }
+}
+
+pub(crate) enum Service {
+    /// Read the receive buffer holding the older frame, this also clears its interrupt flag
+    Receive,
+    /// Report receive buffer overflows, then clear the error interrupt flag
+    Error,
+    /// Nothing to do except clearing the interrupt flags
+    ClearFlags(CANINTF),
+    /// No interrupt reported by `icod`, only the message error flag may be left to report
+    Idle,
+}
+
+/// Decide how to service the interrupt source given by [`CANSTAT::icod`]
+pub(crate) fn service(icod: InterruptFlagCode) -> Service {
+    let flags = CANINTF::new();
+    match icod {
+        InterruptFlagCode::RXB0Interrupt | InterruptFlagCode::RXB1Interrupt => Service::Receive,
+        InterruptFlagCode::TXB0Interrupt => Service::ClearFlags(flags.with_tx0if(true)),
+        InterruptFlagCode::TXB1Interrupt => Service::ClearFlags(flags.with_tx1if(true)),
+        InterruptFlagCode::TXB2Interrupt => Service::ClearFlags(flags.with_tx2if(true)),
+        InterruptFlagCode::ErrorInterrupt => Service::Error,
+        InterruptFlagCode::WakeUpInterrupt => Service::ClearFlags(flags.with_wakif(true)),
+        InterruptFlagCode::NoInterrupt => Service::Idle,
+    }
+}
+
+/// Event of the interrupt source given by [`CANSTAT::icod`], [`EFLG`] still needs to be read
+///
+/// `icod` never reports message errors.
+pub(crate) fn event(icod: InterruptFlagCode) -> Option<InterruptEvent> {
+    let event = match icod {
+        InterruptFlagCode::ErrorInterrupt => InterruptEvent::Error(EFLG::new()),
+        InterruptFlagCode::WakeUpInterrupt => InterruptEvent::WakeUp,
+        InterruptFlagCode::TXB0Interrupt => InterruptEvent::TxComplete(TxBuffer::TXB0),
+        InterruptFlagCode::TXB1Interrupt => InterruptEvent::TxComplete(TxBuffer::TXB1),
+        InterruptFlagCode::TXB2Interrupt => InterruptEvent::TxComplete(TxBuffer::TXB2),
+        InterruptFlagCode::RXB0Interrupt => InterruptEvent::RxReady(RxBuffer::RXB0),
+        InterruptFlagCode::RXB1Interrupt => InterruptEvent::RxReady(RxBuffer::RXB1),
+        InterruptFlagCode::NoInterrupt => return None,
+    };
+    Some(event)
+}
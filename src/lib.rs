@@ -2,8 +2,11 @@
 //!
 //! API is implemented in terms of of the [`embedded_hal`] and [`embedded_can`] traits.
 //!
-//! Activating the `mcp2515` or `mcp25625` feature will enable
-//! additional registers and instructions the MCP2510 does not support.
+//! The chip [`Variant`] is selected at runtime, see [`MCP25xx::with_variant`]
+//! and [`MCP25xx::detect_variant`], or fixed at compile time by the marker types in [`variant`].
+//!
+//! Activating the `async` feature will enable [`asynch::MCP25xx`], a driver
+//! for [`embedded_hal_async::spi::SpiDevice`].
 //!
 //! # Example
 //!
@@ -11,22 +14,24 @@
 //! use embedded_can::nb::Can;
 //! use embedded_can::{Frame, StandardId};
 //! use mcp25xx::bitrates::clock_16mhz::CNF_500K_BPS;
-//! # use mcp25xx::doctesthelper::NoOpSPI;
+//! # use mcp25xx::doctesthelper::{FakeSPI, NoOpDelay};
 //! use mcp25xx::registers::{OperationMode, RXB0CTRL, RXM};
 //! use mcp25xx::{CanFrame, Config, MCP25xx};
 //!
-//! # let spi = NoOpSPI;
+//! # let spi = FakeSPI::default();
+//! # let mut delay = NoOpDelay;
 //! #
 //! // spi is a struct implementing embedded_hal::spi::SpiDevice.
+//! // delay is a struct implementing embedded_hal::delay::DelayNs.
 //!
-//! let mut mcp25xx = MCP25xx { spi };
+//! let mut mcp25xx = MCP25xx::new(spi);
 //!
 //! let config = Config::default()
 //!     .mode(OperationMode::NormalOperation)
 //!     .bitrate(CNF_500K_BPS)
 //!     .receive_buffer_0(RXB0CTRL::default().with_rxm(RXM::ReceiveAny));
 //!
-//! mcp25xx.apply_config(&config).unwrap();
+//! mcp25xx.apply_config(&config, &mut delay).unwrap();
 //!
 //! // Send a frame
 //! let can_id = StandardId::new(123).unwrap();
@@ -43,76 +48,222 @@
 
 #![no_std]
 #![cfg_attr(doc, feature(doc_cfg))]
-use core::fmt::Debug;
-
-pub use config::Config;
+pub use buffered::BufferedReceiver;
+pub use busoff::BusOffRecovery;
+pub use config::{Config, ConfigRegisters, RegisterMismatch};
 pub use embedded_can;
-use embedded_can::{ErrorKind, Frame};
+use embedded_can::{Frame, Id, StandardId};
+use embedded_hal::delay::DelayNs;
 use embedded_hal::spi::{Operation, SpiDevice};
+pub use error::{Error, VerifyError};
+pub use errorstate::{BusState, ErrorState, StateTransition};
 pub use frame::CanFrame;
 pub use idheader::IdHeader;
+pub use interrupt::{InterruptDriven, InterruptError, InterruptEvent};
+pub use snapshot::{RegisterChange, RegisterName, RegisterSnapshot};
+pub use txqueue::TxQueue;
+pub use txstatus::{Transmission, TxStatus};
+pub use variant::Variant;
 
+use crate::busoff::BusOff;
+use crate::config::CONFIG_REGISTERS_LEN;
+use crate::errorstate::{ERROR_REGISTERS_LEN, StateTracker};
+use crate::filters::FilterBank;
 use crate::registers::*;
+use crate::rxorder::RxOrder;
+use crate::snapshot::REGISTER_COUNT;
+use crate::variant::{ChipVariant, Mcp2515Features};
 
-/// Preconfigured CNF registers for 8, 16 and 20 Mhz oscillators
+/// Driver for async SPI devices
+#[cfg(feature = "async")]
+#[cfg_attr(doc, doc(cfg(feature = "async")))]
+pub mod asynch;
+/// Bit timing calculation and preconfigured CNF registers for 8, 10, 12, 16, 20, 24 and 25 Mhz oscillators
 pub mod bitrates;
+/// Masks and filters of the receive buffers and planning them for a set of wanted identifiers
+pub mod filters;
 /// Register bitfields
 pub mod registers;
+/// Driver tracking the operation mode in its type
+pub mod typestate;
+/// Chip variants and the features they support
+pub mod variant;
 
+mod buffered;
+mod busoff;
 mod config;
+mod error;
+mod errorstate;
 mod frame;
 mod idheader;
+mod interrupt;
+mod rxorder;
+mod snapshot;
+mod txqueue;
+mod txstatus;
 
 /// Either a MCP2510, MCP2515 or MCP25625 CAN controller
 ///
+/// Received frames are returned in the order they arrived in, even if they were
+/// split across both receive buffers by the rollover feature (see [`RXB0CTRL::bukt`]).
+///
 /// ## Note about MCP2515 and MCP25625
 /// These chip revisions offer more efficient commands which the MCP2510 does not support.
-/// You can opt in to using these by activating the `mcp2515` or `mcp25625` feature of this crate.
-pub struct MCP25xx<SPI: SpiDevice> {
+/// They are used once the driver knows the variant, see [`MCP25xx::with_variant`]
+/// and [`MCP25xx::detect_variant`].
+/// Chip specific features are only available for the matching [`variant`] types.
+pub struct MCP25xx<SPI: SpiDevice, V: ChipVariant = Variant> {
     pub spi: SPI,
+    rx_order: RxOrder,
+    state_tracker: StateTracker,
+    bus_off: BusOff,
+    /// Transmit buffer whose frame is aborted to make room for a higher priority frame
+    replacing: Option<TxBuffer>,
+    variant: V,
 }
 
 impl<SPI: SpiDevice> MCP25xx<SPI> {
+    pub fn new(spi: SPI) -> Self {
+        MCP25xx::with_variant(spi, Variant::default())
+    }
+
+    /// Probe whether the controller is a MCP2510 or not and use the matching instructions from now on
+    ///
+    /// Only the MCP2515 and MCP25625 implement the one-shot mode bit of [`CANCTRL`], it is set and read back.
+    /// Both share the same controller and are reported as [`Variant::MCP2515`].
+    pub fn detect_variant(&mut self) -> Result<Variant, SPI::Error> {
+        let canctrl: CANCTRL = self.read_register()?;
+        self.modify_register(CANCTRL::new().with_osm(true), ONE_SHOT_MODE)?;
+        let probe: CANCTRL = self.read_register()?;
+        self.modify_register(canctrl, ONE_SHOT_MODE)?;
+        self.variant = if probe.osm() {
+            Variant::MCP2515
+        } else {
+            Variant::MCP2510
+        };
+        Ok(self.variant)
+    }
+}
+
+impl<SPI: SpiDevice, V: ChipVariant> MCP25xx<SPI, V> {
+    /// Driver using the instructions the given chip variant supports
+    ///
+    /// Either one of the marker types in [`variant`] or a [`Variant`] selected at runtime.
+    /// [`MCP25xx::new`] uses [`Variant::default`], which only uses instructions every variant supports.
+    pub fn with_variant(spi: SPI, variant: V) -> Self {
+        MCP25xx {
+            spi,
+            rx_order: RxOrder::default(),
+            state_tracker: StateTracker::default(),
+            bus_off: BusOff::default(),
+            replacing: None,
+            variant,
+        }
+    }
+
+    pub fn variant(&self) -> V {
+        self.variant
+    }
+
     /// Performs the following steps:
+    /// * checks the bit timing of [`Config::cnf`], see [`bitrates::validate`]
     /// * resets the CAN Controller (this resets all registers and puts it into configuration mode)
+    ///   and checks that it responds
     /// * applies configuration
-    /// * applies selected operation mode
+    /// * applies selected operation mode and waits until the controller entered it
     ///
     /// ## Note about Masks
     /// The default state of the mask registers is all zeros, which means, filters get ignored.
     /// You should give values for both mask registers even if you only intend to use one receive buffer.
+    /// [`filters::Rxb0Filters`] and [`filters::Rxb1Filters`] always set the mask
+    /// together with the filters of the receive buffer.
     ///
     /// ```
-    /// # use mcp25xx::doctesthelper::get_mcp25xx;
-    /// # use mcp25xx::{AcceptanceFilter, Config, MCP25xx};
+    /// # use mcp25xx::doctesthelper::{get_mcp25xx, NoOpDelay};
+    /// # use mcp25xx::{Config, MCP25xx};
+    /// # use mcp25xx::filters::{Filter, Mask, Rxb0Filters, Rxb1Filters};
     /// # use mcp25xx::registers::OperationMode;
     /// # use mcp25xx::bitrates::clock_16mhz::CNF_500K_BPS;
     /// # use embedded_can::StandardId;
     ///
     /// let mut mcp25xx: MCP25xx<_> = get_mcp25xx();
+    /// # let mut delay = NoOpDelay;
     ///
     /// let can_id = StandardId::new(123).unwrap();
-    /// let filters = [
-    ///     (AcceptanceFilter::Filter0, can_id.into()),
-    ///     (AcceptanceFilter::Mask0, StandardId::MAX.into()),
-    ///     (AcceptanceFilter::Mask1, StandardId::MAX.into()),
-    /// ];
+    /// let exact = Mask::standard(StandardId::MAX);
     ///
     /// let config = Config::default()
     ///     .mode(OperationMode::NormalOperation)
     ///     .bitrate(CNF_500K_BPS)
-    ///     .filters(&filters);
-    /// mcp25xx.apply_config(&config).unwrap();
+    ///     .receive_buffer_0_filters(Rxb0Filters::new(exact, [can_id.into(); 2]))
+    ///     .receive_buffer_1_filters(Rxb1Filters::new(exact, [can_id.into(); 4]));
+    /// mcp25xx.apply_config(&config, &mut delay).unwrap();
+    /// ```
+    pub fn apply_config(
+        &mut self,
+        config: &Config<'_, V>,
+        delay: &mut impl DelayNs,
+    ) -> Result<(), Error<SPI::Error>> {
+        bitrates::validate(config.cnf).map_err(Error::InvalidBitTiming)?;
+        self.reset_and_wait(delay)?;
+        self.write_config(config).map_err(Error::Spi)?;
+        self.write_register(config.canctrl).map_err(Error::Spi)?;
+        self.wait_for_mode(config.canctrl.reqop(), delay, APPLY_CONFIG_TIMEOUT_MS)
+    }
+
+    /// [`apply_config`](MCP25xx::apply_config), then read back the configuration
+    /// and compare it with the written registers
+    ///
+    /// Read-only bits and registers the configuration does not write are not compared.
+    pub fn apply_config_verified(
+        &mut self,
+        config: &Config<'_, V>,
+        delay: &mut impl DelayNs,
+    ) -> Result<(), VerifyError<SPI::Error>> {
+        self.apply_config(config, delay)?;
+        let read = self.read_config().map_err(Error::Spi)?;
+        let written = read.with_config(config);
+        match read.mismatches(&written).next() {
+            Some(_) => Err(VerifyError::Mismatch { written, read }),
+            None => Ok(()),
+        }
+    }
+
+    /// Read the registers written by [`apply_config`](MCP25xx::apply_config)
+    ///
     /// ```
-    pub fn apply_config(&mut self, config: &Config<'_>) -> Result<(), SPI::Error> {
-        self.reset()?;
-        self.set_bitrate(config.cnf)?;
-        self.write_register(config.rxb0ctrl)?;
-        self.write_register(config.rxb1ctrl)?;
-        for &(filter, id_header) in config.filters {
-            self.set_filter(filter, id_header)?;
+    /// # use mcp25xx::doctesthelper::get_mcp25xx;
+    /// use mcp25xx::{Config, MCP25xx};
+    ///
+    /// let mut mcp25xx: MCP25xx<_> = get_mcp25xx();
+    ///
+    /// let registers = mcp25xx.read_config().unwrap();
+    /// let config: Config = registers.as_config();
+    /// ```
+    pub fn read_config(&mut self) -> Result<ConfigRegisters, SPI::Error> {
+        // Filter0 to Filter2, Filter3 to Filter5, Mask0 to CANINTE
+        let mut buf = [0; CONFIG_REGISTERS_LEN];
+        let (filters, masks) = buf.split_at_mut(6 * AcceptanceFilter::LEN);
+        let (filter0, filter3) = filters.split_at_mut(3 * AcceptanceFilter::LEN);
+        self.read_registers(AcceptanceFilter::Filter0.address(), filter0)?;
+        self.read_registers(AcceptanceFilter::Filter3.address(), filter3)?;
+        self.read_registers(AcceptanceFilter::Mask0.address(), masks)?;
+        Ok(ConfigRegisters::from_bytes(
+            buf,
+            self.read_register()?,
+            self.read_register()?,
+            self.read_register()?,
+        ))
+    }
+
+    /// Everything of [`Config`] except [`CANCTRL`], requires Configuration mode
+    pub(crate) fn write_config(&mut self, config: &Config<'_, V>) -> Result<(), SPI::Error> {
+        self.bus_off = BusOff::new(config.bus_off_recovery);
+        self.replacing = None;
+        for write in config.register_writes() {
+            self.write_registers(write.address, write.data())?;
         }
-        self.write_register(config.canctrl)
+        Ok(())
     }
 
     /// Set the controller to NormalOperation, Sleep, Loopback, ListenOnly or Configuration
@@ -121,20 +272,73 @@ impl<SPI: SpiDevice> MCP25xx<SPI> {
         self.modify_register(reg, 0b11100000)
     }
 
+    /// Set the operation mode and wait until [`CANSTAT::opmod`] reports it
+    ///
+    /// The controller only switches modes once pending transmissions finished.
+    /// [`CANSTAT`] is polled every 100 µs, [`Error::ModeTimeout`] is returned if the controller
+    /// did not switch within `timeout_ms` and [`Error::ModeMismatch`] if it switched to another mode.
+    pub fn set_mode_and_wait(
+        &mut self,
+        mode: OperationMode,
+        delay: &mut impl DelayNs,
+        timeout_ms: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.set_mode(mode).map_err(Error::Spi)?;
+        self.wait_for_mode(mode, delay, timeout_ms)
+    }
+
+    fn wait_for_mode(
+        &mut self,
+        mode: OperationMode,
+        delay: &mut impl DelayNs,
+        timeout_ms: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.poll_mode(mode, delay, polls(timeout_ms))
+    }
+
+    /// Read [`CANSTAT`] until it reports `mode`, at most `polls` more times
+    /// with [`POLL_INTERVAL_US`] in between
+    fn poll_mode(
+        &mut self,
+        mode: OperationMode,
+        delay: &mut impl DelayNs,
+        polls: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        let mut initial = None;
+        let mut polled = 0;
+        loop {
+            let canstat: CANSTAT = self.read_register().map_err(Error::Spi)?;
+            let opmod = canstat.opmod();
+            if opmod == mode {
+                return Ok(());
+            }
+            if *initial.get_or_insert(opmod) != opmod {
+                return Err(Error::ModeMismatch(opmod));
+            }
+            if polled == polls {
+                return Err(Error::ModeTimeout(opmod));
+            }
+            delay.delay_us(POLL_INTERVAL_US);
+            polled += 1;
+        }
+    }
+
     /// Set clock settings
     ///
     /// See [`bitrates`] for preconfigured settings for different oscillator frequencies.
     ///
     /// ## Note:
-    /// The controller needs to be in Configuration Mode for this
+    /// The controller needs to be in Configuration Mode for this,
+    /// [`typestate::MCP25xx`] enforces it at compile time
     pub fn set_bitrate(&mut self, cnf: CNF) -> Result<(), SPI::Error> {
-        self.write_registers(CNF3::ADDRESS, &cnf.into_bytes())
+        self.write_registers(cnf.address(), &cnf.into_bytes())
     }
 
     /// Set individual receive buffer filters or masks
     ///
     /// ## Note:
-    /// The controller needs to be in Configuration Mode for this
+    /// The controller needs to be in Configuration Mode for this,
+    /// [`typestate::MCP25xx`] enforces it at compile time
     ///
     /// ```
     /// # use mcp25xx::doctesthelper::get_mcp25xx;
@@ -150,12 +354,37 @@ impl<SPI: SpiDevice> MCP25xx<SPI> {
     /// mcp25xx.set_filter(Filter0, IdHeader::from(std_id)).unwrap();
     ///
     /// mcp25xx.set_filter(Filter2, IdHeader::from(ext_id)).unwrap();
-    /// #[cfg(any(feature = "mcp2515", feature = "mcp25625"))]
-    /// mcp25xx.set_filter(Filter3, IdHeader::with_two_data_bytes(std_id, [4, 5])).unwrap();
     ///
     /// ```
     pub fn set_filter(&mut self, filter: AcceptanceFilter, id: IdHeader) -> Result<(), SPI::Error> {
-        self.write_registers(filter as u8, &id.into_bytes())
+        self.write_registers(filter.address(), &id.into_bytes())
+    }
+
+    /// Set the mask and all filters of a receive buffer
+    ///
+    /// ## Note:
+    /// The controller needs to be in Configuration Mode for this,
+    /// [`typestate::MCP25xx`] enforces it at compile time
+    ///
+    /// ```
+    /// # use mcp25xx::doctesthelper::get_mcp25xx;
+    /// use embedded_can::{StandardId, ExtendedId};
+    /// use mcp25xx::MCP25xx;
+    /// use mcp25xx::filters::{Filter, Mask, Rxb1Filters};
+    ///
+    /// let mut mcp25xx: MCP25xx<_> = get_mcp25xx();
+    ///
+    /// let ext_id = ExtendedId::new(0x18FE_F100).unwrap();
+    /// // accept 0x18FEF100 to 0x18FEF1FF
+    /// let mask = Mask::extended(ExtendedId::new(0x1FFF_FF00).unwrap());
+    ///
+    /// mcp25xx.set_filter_bank(&Rxb1Filters::new(mask, [Filter::extended(ext_id); 4])).unwrap();
+    /// ```
+    pub fn set_filter_bank(&mut self, bank: &impl FilterBank) -> Result<(), SPI::Error> {
+        for (filter, id) in bank.registers() {
+            self.set_filter(filter, id)?;
+        }
+        Ok(())
     }
 
     /// Read status flags
@@ -173,70 +402,673 @@ impl<SPI: SpiDevice> MCP25xx<SPI> {
         self.spi.write(&[Instruction::Reset as u8])
     }
 
+    /// Reset the controller, wait for the oscillator to start and check that a controller responds
+    ///
+    /// Returns [`Error::DeviceNotFound`] if [`CANSTAT`] and [`CANCTRL`] do not read back their reset values.
+    pub fn reset_and_wait(&mut self, delay: &mut impl DelayNs) -> Result<(), Error<SPI::Error>> {
+        self.reset().map_err(Error::Spi)?;
+        delay.delay_us(RESET_DELAY_US);
+        let mut regs = [0; 2];
+        self.read_registers(CANSTAT::ADDRESS, &mut regs)
+            .map_err(Error::Spi)?;
+        if regs != RESET_VALUES {
+            return Err(Error::DeviceNotFound);
+        }
+        Ok(())
+    }
+
+    /// Read [`TEC`], [`REC`] and [`EFLG`] in a single SPI transaction
+    ///
+    /// ```
+    /// # use mcp25xx::doctesthelper::get_mcp25xx;
+    /// use mcp25xx::BusState;
+    ///
+    /// let mut mcp25xx = get_mcp25xx();
+    ///
+    /// let state = mcp25xx.error_state().unwrap();
+    /// if state.bus_state == BusState::ErrorPassive {
+    ///     // ...
+    /// }
+    /// ```
+    pub fn error_state(&mut self) -> Result<ErrorState, SPI::Error> {
+        let mut bytes = [0; ERROR_REGISTERS_LEN];
+        self.read_registers(TEC::ADDRESS, &mut bytes)?;
+        Ok(ErrorState::from_bytes(bytes))
+    }
+
+    /// Read the error state and report if the [`BusState`] changed since the last call
+    ///
+    /// The first call compares against [`BusState::ErrorActive`], the state after a reset.
+    pub fn poll_error_state(&mut self) -> Result<Option<StateTransition>, SPI::Error> {
+        let state = self.error_state()?;
+        Ok(self.state_tracker.update(state))
+    }
+
+    /// Handle the interrupt source with the highest priority and clear its interrupt flag
+    ///
+    /// The source is read from [`CANSTAT::icod`], which only reports interrupts enabled
+    /// in [`CANINTE`], see [`Config::interrupts`]. Message errors are reported once `icod`
+    /// reports no more interrupts. Returns `None` if no interrupt is pending.
+    ///
+    /// [`InterruptEvent::RxReady`] is reported until the frame was read with
+    /// [`read_rx_buffer`](MCP25xx::read_rx_buffer), which frees the receive buffer.
+    /// [`InterruptEvent::Error`] also clears the overflow flags of the [`EFLG`] register
+    /// and applies the [`BusOffRecovery`] policy if `txbo` is set.
+    ///
+    /// ```
+    /// # use mcp25xx::doctesthelper::get_mcp25xx;
+    /// use mcp25xx::InterruptEvent;
+    ///
+    /// let mut mcp25xx = get_mcp25xx();
+    ///
+    /// // after the INT pin went low
+    /// while let Some(event) = mcp25xx.handle_interrupt().unwrap() {
+    ///     match event {
+    ///         InterruptEvent::RxReady(buf_idx) => {
+    ///             let frame = mcp25xx.read_rx_buffer(buf_idx).unwrap();
+    ///         }
+    ///         InterruptEvent::TxComplete(buf_idx) => {}
+    ///         InterruptEvent::Error(eflg) => {}
+    ///         InterruptEvent::WakeUp => {}
+    ///         InterruptEvent::MessageError => {}
+    ///     }
+    /// }
+    /// ```
+    pub fn handle_interrupt(&mut self) -> Result<Option<InterruptEvent>, Error<SPI::Error>> {
+        let canstat: CANSTAT = self.read_register().map_err(Error::Spi)?;
+        let event = match interrupt::event(canstat.icod()) {
+            Some(InterruptEvent::Error(_)) => {
+                let eflg: EFLG = self.read_register().map_err(Error::Spi)?;
+                self.track_bus_off(eflg)?;
+                let overflows = u8::from(eflg) & OVERFLOW_FLAGS;
+                if overflows != 0 {
+                    self.modify_register(EFLG::new(), overflows)
+                        .map_err(Error::Spi)?;
+                }
+                InterruptEvent::Error(eflg)
+            }
+            Some(event) => event,
+            None => {
+                let intf: CANINTF = self.read_register().map_err(Error::Spi)?;
+                if !intf.merrf() {
+                    return Ok(None);
+                }
+                InterruptEvent::MessageError
+            }
+        };
+        if event.flags() != 0 {
+            self.modify_register(CANINTF::new(), event.flags())
+                .map_err(Error::Spi)?;
+        }
+        Ok(Some(event))
+    }
+
+    /// Read all 128 registers in a single SPI transaction
+    ///
+    /// ```
+    /// # use mcp25xx::doctesthelper::get_mcp25xx;
+    /// let mut mcp25xx = get_mcp25xx();
+    ///
+    /// let before = mcp25xx.snapshot().unwrap();
+    /// // ...
+    /// let after = mcp25xx.snapshot().unwrap();
+    /// for change in before.diff(&after) {
+    ///     println!("{change}");
+    /// }
+    /// println!("{after}");
+    /// println!("{after:x}");
+    /// ```
+    pub fn snapshot(&mut self) -> Result<RegisterSnapshot, SPI::Error> {
+        let mut bytes = [0; REGISTER_COUNT];
+        // the address space starts with RXF0SIDH
+        self.read_registers(AcceptanceFilter::Filter0.address(), &mut bytes)?;
+        Ok(RegisterSnapshot { bytes })
+    }
+
+    /// Switch the controller back to NormalOperation after the [`BusOffRecovery`] policy held it off the bus
+    ///
+    /// Waits for the delay of [`BusOffRecovery::Delayed`] first and restarts counting
+    /// [`BusOffRecovery::Attempts`]. Does nothing if the controller is not held off the bus.
+    /// Like [`MCP25xx::set_mode_and_wait`], [`Error::ModeTimeout`] is returned if the controller
+    /// did not switch within `timeout_ms`.
+    ///
+    /// ```
+    /// # use mcp25xx::doctesthelper::{get_mcp25xx, NoOpDelay};
+    /// use embedded_can::nb::Can;
+    /// use mcp25xx::bitrates::clock_16mhz::CNF_500K_BPS;
+    /// use mcp25xx::registers::OperationMode;
+    /// use mcp25xx::{BusOffRecovery, CanFrame, Config, Error};
+    ///
+    /// let mut mcp25xx = get_mcp25xx();
+    /// # let mut delay = NoOpDelay;
+    /// // delay is a struct implementing embedded_hal::delay::DelayNs.
+    /// let config = Config::default()
+    ///     .mode(OperationMode::NormalOperation)
+    ///     .bitrate(CNF_500K_BPS)
+    ///     .bus_off_recovery(BusOffRecovery::Delayed(500));
+    /// mcp25xx.apply_config(&config, &mut delay).unwrap();
+    ///
+    /// # let frame = CanFrame::default();
+    /// if let Err(nb::Error::Other(Error::BusOff)) = mcp25xx.transmit(&frame) {
+    ///     mcp25xx.recover_from_bus_off(&mut delay, 10).unwrap();
+    /// }
+    /// ```
+    pub fn recover_from_bus_off(
+        &mut self,
+        delay: &mut impl DelayNs,
+        timeout_ms: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        if !self.bus_off.held {
+            return Ok(());
+        }
+        delay.delay_ms(self.bus_off.rejoin());
+        self.set_mode_and_wait(OperationMode::NormalOperation, delay, timeout_ms)
+    }
+
+    /// Find the bitrate of the bus among the `candidates`
+    ///
+    /// Each candidate is tried in ListenOnly mode, so the controller never disturbs the bus.
+    /// [`CANINTF`] is watched for `window_ms` milliseconds, the first candidate which received
+    /// frames without setting `merrf` is returned. Received frames are discarded and the
+    /// controller is left in Configuration mode, ready for [`MCP25xx::apply_config`].
+    ///
+    /// Each mode change may wait for a frame on the bus to end, so `timeout_ms` needs to cover
+    /// the longest frame at the slowest candidate, otherwise [`Error::ModeTimeout`] is returned.
+    ///
+    /// Both receive buffers accept any frame while detecting, filters set before do not hide
+    /// frames. Their receive modes are restored afterwards.
+    ///
+    /// ```
+    /// # use mcp25xx::doctesthelper::{get_mcp25xx, NoOpDelay};
+    /// use mcp25xx::bitrates::clock_16mhz::{CNF_125K_BPS, CNF_250K_BPS, CNF_500K_BPS};
+    ///
+    /// let mut mcp25xx = get_mcp25xx();
+    /// # let mut delay = NoOpDelay;
+    /// let candidates = [CNF_500K_BPS, CNF_250K_BPS, CNF_125K_BPS];
+    /// if let Some(cnf) = mcp25xx.detect_bitrate(&candidates, &mut delay, 100, 10).unwrap() {
+    ///     // ...
+    /// }
+    /// ```
+    pub fn detect_bitrate(
+        &mut self,
+        candidates: &[CNF],
+        delay: &mut impl DelayNs,
+        window_ms: u32,
+        timeout_ms: u32,
+    ) -> Result<Option<CNF>, Error<SPI::Error>> {
+        for &cnf in candidates {
+            bitrates::validate(cnf).map_err(Error::InvalidBitTiming)?;
+        }
+        self.set_mode_and_wait(OperationMode::Configuration, delay, timeout_ms)?;
+        let rxb0ctrl: RXB0CTRL = self.read_register().map_err(Error::Spi)?;
+        let rxb1ctrl: RXB1CTRL = self.read_register().map_err(Error::Spi)?;
+        self.write_register(rxb0ctrl.with_rxm(RXM::ReceiveAny))
+            .map_err(Error::Spi)?;
+        self.write_register(rxb1ctrl.with_rxm(RXM::ReceiveAny))
+            .map_err(Error::Spi)?;
+        let detected = self.try_bitrates(candidates, delay, window_ms, timeout_ms);
+        let restored = self
+            .write_register(rxb0ctrl)
+            .and_then(|()| self.write_register(rxb1ctrl));
+        let cnf = detected?;
+        restored.map_err(Error::Spi)?;
+        Ok(cnf)
+    }
+
+    /// Try the `candidates` one after another, starting in Configuration mode
+    fn try_bitrates(
+        &mut self,
+        candidates: &[CNF],
+        delay: &mut impl DelayNs,
+        window_ms: u32,
+        timeout_ms: u32,
+    ) -> Result<Option<CNF>, Error<SPI::Error>> {
+        self.clear_detection_flags().map_err(Error::Spi)?;
+        for &cnf in candidates {
+            self.set_bitrate(cnf).map_err(Error::Spi)?;
+            self.set_mode_and_wait(OperationMode::ListenOnly, delay, timeout_ms)?;
+            let received = self.listen(delay, window_ms).map_err(Error::Spi)?;
+            self.set_mode_and_wait(OperationMode::Configuration, delay, timeout_ms)?;
+            self.clear_detection_flags().map_err(Error::Spi)?;
+            if received {
+                return Ok(Some(cnf));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Whether frames were received without message errors within `window_ms`
+    fn listen(&mut self, delay: &mut impl DelayNs, window_ms: u32) -> Result<bool, SPI::Error> {
+        let mut received = false;
+        let mut waited_ms = 0;
+        loop {
+            let intf: CANINTF = self.read_register()?;
+            if intf.merrf() {
+                return Ok(false);
+            }
+            received |= intf.rx0if() || intf.rx1if();
+            if waited_ms >= window_ms {
+                return Ok(received);
+            }
+            delay.delay_ms(1);
+            waited_ms += 1;
+        }
+    }
+
+    /// Discard received frames and message errors
+    fn clear_detection_flags(&mut self) -> Result<(), SPI::Error> {
+        self.rx_order = RxOrder::default();
+        self.modify_register(CANINTF::new(), BITRATE_DETECTION_FLAGS)
+    }
+}
+
+impl<SPI: SpiDevice, V: Mcp2515Features> MCP25xx<SPI, V> {
     /// Read receive buffer status flags
-    #[cfg(any(feature = "mcp2515", feature = "mcp25625"))]
-    #[cfg_attr(doc, doc(cfg(any(feature = "mcp2515", feature = "mcp25625"))))]
-    pub fn rx_status(&mut self) -> Result<RxStatusResponse, SPI::Error> {
+    ///
+    /// Returns [`Error::Unsupported`] if a [`Variant::MCP2510`] was selected at runtime.
+    pub fn rx_status(&mut self) -> Result<RxStatusResponse, Error<SPI::Error>> {
+        if !self.variant.has_buffer_instructions() {
+            return Err(Error::Unsupported);
+        }
         let mut buf = [0];
-        self.spi.transaction(&mut [
-            Operation::Write(&[Instruction::RxStatus as u8]),
-            Operation::Read(&mut buf),
-        ])?;
+        self.spi
+            .transaction(&mut [
+                Operation::Write(&[Instruction::RxStatus as u8]),
+                Operation::Read(&mut buf),
+            ])
+            .map_err(Error::Spi)?;
         Ok(RxStatusResponse::from_bytes(buf))
     }
-}
-
-#[derive(Debug)]
-pub struct SpiError<E>(pub E);
 
-impl<E: Debug> embedded_can::Error for SpiError<E> {
-    fn kind(&self) -> ErrorKind {
-        ErrorKind::Other
+    /// Set a filter or mask comparing standard identifiers and the first two data bytes
+    ///
+    /// The data bytes are compared with the bits of an extended identifier,
+    /// so the mask of the receive buffer needs to cover them, see [`filters::Mask`].
+    ///
+    /// ## Note:
+    /// The controller needs to be in Configuration Mode for this,
+    /// [`typestate::MCP25xx`] enforces it at compile time
+    ///
+    /// ```
+    /// # use mcp25xx::doctesthelper::get_mcp25xx;
+    /// use embedded_can::StandardId;
+    /// use mcp25xx::{MCP25xx, AcceptanceFilter::*};
+    ///
+    /// let mut mcp25xx: MCP25xx<_> = get_mcp25xx();
+    ///
+    /// let std_id = StandardId::new(1234).unwrap();
+    ///
+    /// mcp25xx.set_data_byte_filter(Mask1, StandardId::MAX, [0xFF, 0xFF]).unwrap();
+    /// mcp25xx.set_data_byte_filter(Filter3, std_id, [4, 5]).unwrap();
+    /// ```
+    pub fn set_data_byte_filter(
+        &mut self,
+        filter: AcceptanceFilter,
+        id: StandardId,
+        bytes: [u8; 2],
+    ) -> Result<(), SPI::Error> {
+        self.set_filter(filter, IdHeader::with_two_data_bytes(id, bytes))
     }
 }
 
-impl<SPI: SpiDevice> embedded_can::nb::Can for MCP25xx<SPI> {
+impl<SPI: SpiDevice, V: ChipVariant> embedded_can::nb::Can for MCP25xx<SPI, V> {
     type Frame = CanFrame;
-    type Error = SpiError<SPI::Error>;
+    type Error = Error<SPI::Error>;
+
+    /// Load the frame into a free transmit buffer and request it to be sent
+    ///
+    /// If all transmit buffers are pending, the lowest priority frame gets replaced
+    /// if it has a lower priority than `frame`. [`nb::Error::WouldBlock`] is returned
+    /// while that frame is still being transmitted, the next call loads `frame` once its
+    /// transmit buffer is free.
+    /// Otherwise, [`Error::BusOff`] or [`Error::Transmit`] is returned if a pending frame failed.
+    /// See [`MCP25xx::transmit_tracked`] to learn which transmit buffer was used.
+    fn transmit(&mut self, frame: &Self::Frame) -> nb::Result<Option<Self::Frame>, Self::Error> {
+        self.transmit_tracked(frame)
+            .map(|transmission| transmission.replaced)
+    }
+
+    /// Read the oldest received frame
+    ///
+    /// Once both receive buffers are empty, [`Error::Overrun`] and [`Error::MessageError`]
+    /// are reported and their flags cleared, followed by [`Error::BusOff`] while the
+    /// [`BusOffRecovery`] policy holds the controller off the bus.
+    fn receive(&mut self) -> nb::Result<Self::Frame, Self::Error> {
+        let intf: CANINTF = self.read_register().map_err(Error::Spi)?;
+        if let Some(buf_idx) = self.rx_order.next(intf.rx0if(), intf.rx1if()) {
+            let frame = self.read_rx_buffer(buf_idx).map_err(Error::Spi)?;
+            if matches!(buf_idx, RxBuffer::RXB0) && !intf.rx1if() {
+                let intf: CANINTF = self.read_register().map_err(Error::Spi)?;
+                self.rx_order.rxb0_freed(intf.rx1if());
+            }
+            return Ok(frame);
+        }
+        if intf.errif() {
+            let eflg: EFLG = self.read_register().map_err(Error::Spi)?;
+            self.track_bus_off(eflg)?;
+            if let Some((buf_idx, flag)) = overflowed_rx_buffer(eflg) {
+                self.modify_register(EFLG::new(), flag)
+                    .map_err(Error::Spi)?;
+                self.modify_register(CANINTF::new(), ERROR_FLAG)
+                    .map_err(Error::Spi)?;
+                return Err(nb::Error::Other(Error::Overrun(buf_idx)));
+            }
+        }
+        if intf.merrf() {
+            self.modify_register(CANINTF::new(), MESSAGE_ERROR_FLAG)
+                .map_err(Error::Spi)?;
+            return Err(nb::Error::Other(Error::MessageError));
+        }
+        if self.bus_off.held {
+            return Err(nb::Error::Other(Error::BusOff));
+        }
+        Err(nb::Error::WouldBlock)
+    }
+}
 
-    fn transmit(
+impl<SPI: SpiDevice, V: ChipVariant> MCP25xx<SPI, V> {
+    /// [`embedded_can::nb::Can::transmit`], which also returns the transmit buffer
+    /// holding the frame
+    ///
+    /// ```
+    /// # use mcp25xx::doctesthelper::get_mcp25xx;
+    /// use embedded_can::{Frame, StandardId};
+    /// use mcp25xx::{CanFrame, TxStatus};
+    ///
+    /// let mut mcp25xx = get_mcp25xx();
+    ///
+    /// let frame = CanFrame::new(StandardId::new(123).unwrap(), &[1, 2, 3]).unwrap();
+    /// let transmission = nb::block!(mcp25xx.transmit_tracked(&frame)).unwrap();
+    /// // ...
+    /// if mcp25xx.tx_status(transmission.buffer).unwrap() == TxStatus::Sent {
+    ///     // ...
+    /// }
+    /// ```
+    pub fn transmit_tracked(
         &mut self,
-        frame: &Self::Frame,
-    ) -> nb::Result<Option<Self::Frame>, SpiError<SPI::Error>> {
-        let status = self.read_status().map_err(SpiError)?;
+        frame: &CanFrame,
+    ) -> nb::Result<Transmission, Error<SPI::Error>> {
+        if self.bus_off.held {
+            return Err(nb::Error::Other(Error::BusOff));
+        }
+        if let Some(buf_idx) = self.replacing {
+            return self.finish_replacement(buf_idx, frame);
+        }
+
+        let status = self.read_status().map_err(Error::Spi)?;
         let mut buf_idx = TxBuffer::TXB0;
         if status.txreq0() {
             buf_idx = TxBuffer::TXB1;
             if status.txreq1() {
                 buf_idx = TxBuffer::TXB2;
                 if status.txreq2() {
-                    // TODO replace a pending lower priority frame
-                    return Err(nb::Error::WouldBlock);
+                    return self.replace_pending_frame(frame);
                 }
             }
         }
 
-        self.load_tx_buffer(buf_idx, frame).map_err(SpiError)?;
-        self.request_to_send(buf_idx).map_err(SpiError)?;
+        self.load_tx_buffer(buf_idx, frame).map_err(Error::Spi)?;
+        self.request_to_send(buf_idx).map_err(Error::Spi)?;
+        self.bus_off.sent();
+        Ok(Transmission {
+            buffer: buf_idx,
+            replaced: None,
+        })
+    }
+
+    /// Read the state of the frame in the transmit buffer
+    ///
+    /// [`CANINTF`] is only read if TXBnCTRL reports no pending frame.
+    pub fn tx_status(&mut self, buf_idx: TxBuffer) -> Result<TxStatus, SPI::Error> {
+        let ctrl = self.read_tx_control(buf_idx)?;
+        if let Some(status) = TxStatus::from_control(ctrl) {
+            return Ok(status);
+        }
+        let intf: CANINTF = self.read_register()?;
+        Ok(TxStatus::from_flags(ctrl, intf, buf_idx))
+    }
+
+    /// Abort the frame in the transmit buffer and give it back
+    ///
+    /// Returns `None` if the buffer was not pending or the frame was sent before it could be
+    /// aborted, a frame which is already being transmitted is not interrupted.
+    /// TXBnCTRL is polled every 100 µs until the buffer is no longer pending,
+    /// [`Error::AbortTimeout`] is returned if it still is after `timeout_ms`.
+    ///
+    /// ```
+    /// # use mcp25xx::doctesthelper::{get_mcp25xx, NoOpDelay};
+    /// use embedded_can::{Frame, StandardId};
+    /// use mcp25xx::CanFrame;
+    ///
+    /// let mut mcp25xx = get_mcp25xx();
+    /// # let mut delay = NoOpDelay;
+    ///
+    /// let frame = CanFrame::new(StandardId::new(123).unwrap(), &[1, 2, 3]).unwrap();
+    /// let transmission = nb::block!(mcp25xx.transmit_tracked(&frame)).unwrap();
+    /// if let Some(frame) = mcp25xx.abort(transmission.buffer, &mut delay, 10).unwrap() {
+    ///     // ...
+    /// }
+    /// ```
+    pub fn abort(
+        &mut self,
+        buf_idx: TxBuffer,
+        delay: &mut impl DelayNs,
+        timeout_ms: u32,
+    ) -> Result<Option<CanFrame>, Error<SPI::Error>> {
+        // the frame of an unfinished replacement is given back here instead
+        let replacing = self
+            .replacing
+            .take_if(|replacing| *replacing as u8 == buf_idx as u8)
+            .is_some();
+        // `abtf` of a frame aborted before stays set until the buffer is requested again
+        if !replacing && !self.read_tx_control(buf_idx).map_err(Error::Spi)?.txreq() {
+            return Ok(None);
+        }
+        if self
+            .abort_tx_buffer(buf_idx, delay, polls(timeout_ms))?
+            .abtf()
+        {
+            return Ok(Some(self.read_tx_buffer(buf_idx).map_err(Error::Spi)?));
+        }
         Ok(None)
     }
 
-    fn receive(&mut self) -> nb::Result<Self::Frame, SpiError<SPI::Error>> {
-        // TODO look at https://www.microchip.com/forums/tm.aspx?m=620741
-        let status = self.read_status().map_err(SpiError)?;
-        if status.rx0if() {
-            Ok(self.read_rx_buffer(RxBuffer::RXB0).map_err(SpiError)?)
-        } else if status.rx1if() {
-            Ok(self.read_rx_buffer(RxBuffer::RXB1).map_err(SpiError)?)
+    /// Abort the frames of all transmit buffers with `abat` of the [`CANCTRL`] register
+    /// and give them back
+    ///
+    /// The returned frames are indexed by [`TxBuffer`], `None` for buffers which were not
+    /// pending or whose frame was sent before it could be aborted.
+    /// Like [`MCP25xx::abort`], [`Error::AbortTimeout`] is returned if a buffer is still pending
+    /// after `timeout_ms`, `abat` is cleared either way.
+    pub fn abort_all(
+        &mut self,
+        delay: &mut impl DelayNs,
+        timeout_ms: u32,
+    ) -> Result<[Option<CanFrame>; 3], Error<SPI::Error>> {
+        let mut status = self.read_status().map_err(Error::Spi)?;
+        let mut pending = [status.txreq0(), status.txreq1(), status.txreq2()];
+        if let Some(buf_idx) = self.replacing.take() {
+            pending[buf_idx as usize] = true;
+        }
+        self.modify_register(CANCTRL::new().with_abat(true), ABORT_ALL)
+            .map_err(Error::Spi)?;
+        // a frame which is currently being transmitted can not be aborted anymore
+        let polls = polls(timeout_ms);
+        let mut polled = 0;
+        let mut timed_out = None;
+        while let Some(buf_idx) = pending_tx_buffer(&status) {
+            if polled > polls {
+                timed_out = Some(buf_idx);
+                break;
+            }
+            if polled > 0 {
+                delay.delay_us(POLL_INTERVAL_US);
+            }
+            status = self.read_status().map_err(Error::Spi)?;
+            polled += 1;
+        }
+        self.modify_register(CANCTRL::new(), ABORT_ALL)
+            .map_err(Error::Spi)?;
+        if let Some(buf_idx) = timed_out {
+            return Err(Error::AbortTimeout(buf_idx));
+        }
+
+        let mut frames = [None, None, None];
+        for (buf_idx, txreq) in TxBuffer::ALL.into_iter().zip(pending) {
+            if txreq && self.read_tx_control(buf_idx).map_err(Error::Spi)?.abtf() {
+                frames[buf_idx as usize] = Some(self.read_tx_buffer(buf_idx).map_err(Error::Spi)?);
+            }
+        }
+        Ok(frames)
+    }
+
+    /// Abort the lowest priority pending frame if it has a lower priority than `frame`
+    /// and load `frame` into its transmit buffer instead
+    fn replace_pending_frame(
+        &mut self,
+        frame: &CanFrame,
+    ) -> nb::Result<Transmission, Error<SPI::Error>> {
+        let mut lowest: Option<(TxBuffer, Id)> = None;
+        let mut failed = None;
+        for buf_idx in TxBuffer::ALL {
+            let (ctrl, id) = self.read_tx_header(buf_idx).map_err(Error::Spi)?;
+            if ctrl.txerr() && failed.is_none() {
+                failed = Some(buf_idx);
+            }
+            if lowest.is_none_or(|(_, lowest)| id > lowest) {
+                lowest = Some((buf_idx, id));
+            }
+        }
+        if failed.is_some() {
+            let eflg: EFLG = self.read_register().map_err(Error::Spi)?;
+            self.track_bus_off(eflg)?;
+            if eflg.txbo() {
+                return Err(nb::Error::Other(Error::BusOff));
+            }
+        }
+        let (buf_idx, id) = lowest.unwrap();
+        if frame.id() >= id {
+            return match failed {
+                Some(buf_idx) => Err(nb::Error::Other(Error::Transmit(buf_idx))),
+                None => Err(nb::Error::WouldBlock),
+            };
+        }
+
+        self.bit_modify(TXBnCTRL(buf_idx).address(), TXREQ, 0)
+            .map_err(Error::Spi)?;
+        self.replacing = Some(buf_idx);
+        self.finish_replacement(buf_idx, frame)
+    }
+
+    /// Load `frame` into the transmit buffer of the aborted frame once it is no longer pending
+    ///
+    /// A frame which is currently being transmitted can not be aborted anymore,
+    /// [`nb::Error::WouldBlock`] is returned until it was sent or failed.
+    fn finish_replacement(
+        &mut self,
+        buf_idx: TxBuffer,
+        frame: &CanFrame,
+    ) -> nb::Result<Transmission, Error<SPI::Error>> {
+        let ctrl = self.read_tx_control(buf_idx).map_err(Error::Spi)?;
+        if ctrl.txreq() {
+            return Err(nb::Error::WouldBlock);
+        }
+        self.replacing = None;
+        let replaced = if ctrl.abtf() {
+            Some(self.read_tx_buffer(buf_idx).map_err(Error::Spi)?)
         } else {
-            Err(nb::Error::WouldBlock)
+            None
+        };
+
+        self.load_tx_buffer(buf_idx, frame).map_err(Error::Spi)?;
+        self.request_to_send(buf_idx).map_err(Error::Spi)?;
+        self.bus_off.sent();
+        Ok(Transmission {
+            buffer: buf_idx,
+            replaced,
+        })
+    }
+
+    /// Apply the [`BusOffRecovery`] policy once `txbo` is set, record its clearing otherwise
+    fn track_bus_off(&mut self, eflg: EFLG) -> Result<(), Error<SPI::Error>> {
+        if !eflg.txbo() {
+            self.bus_off.recovered();
+            return Ok(());
+        }
+        if self.bus_off.active {
+            return Ok(());
         }
+        self.enter_bus_off()
+    }
+
+    /// Abort all pending frames and hold the controller off the bus if the recovery policy says so
+    fn enter_bus_off(&mut self) -> Result<(), Error<SPI::Error>> {
+        let status = self.read_status().map_err(Error::Spi)?;
+        let pending = [status.txreq0(), status.txreq1(), status.txreq2()];
+        for (buf_idx, txreq) in TxBuffer::ALL.into_iter().zip(pending) {
+            if txreq {
+                self.abort_tx_buffer(buf_idx, &mut NoDelay, ABORT_POLLS)?;
+            }
+        }
+        if self.bus_off.enter() {
+            self.set_mode(OperationMode::Configuration)
+                .map_err(Error::Spi)?;
+            self.poll_mode(
+                OperationMode::Configuration,
+                &mut NoDelay,
+                BUS_OFF_MODE_POLLS,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Clear `txreq` of the selected transmit buffer and wait until it is no longer pending
+    ///
+    /// TXBnCTRL is read at most `polls` more times with [`POLL_INTERVAL_US`] in between.
+    /// `abtf` of the returned control register tells whether the frame was aborted or sent.
+    pub(crate) fn abort_tx_buffer(
+        &mut self,
+        buf_idx: TxBuffer,
+        delay: &mut impl DelayNs,
+        polls: u32,
+    ) -> Result<TXB0CTRL, Error<SPI::Error>> {
+        self.bit_modify(TXBnCTRL(buf_idx).address(), TXREQ, 0)
+            .map_err(Error::Spi)?;
+        let mut polled = 0;
+        loop {
+            // a frame which is currently being transmitted can not be aborted anymore
+            let ctrl = self.read_tx_control(buf_idx).map_err(Error::Spi)?;
+            if !ctrl.txreq() {
+                return Ok(ctrl);
+            }
+            if polled == polls {
+                return Err(Error::AbortTimeout(buf_idx));
+            }
+            delay.delay_us(POLL_INTERVAL_US);
+            polled += 1;
+        }
+    }
+
+    /// Read the control register and the CAN ID of the selected transmit buffer
+    fn read_tx_header(&mut self, buf_idx: TxBuffer) -> Result<(TXB0CTRL, Id), SPI::Error> {
+        let mut bytes = [0; 5];
+        self.read_registers(TXBnCTRL(buf_idx).address(), &mut bytes)?;
+        let [ctrl, sidh, sidl, eid8, eid0] = bytes;
+        let id = IdHeader::from_bytes([sidh, sidl, eid8, eid0]).id();
+        Ok((TXB0CTRL::from_bytes([ctrl]), id))
+    }
+
+    /// All TXBnCTRL registers share the layout of [`TXB0CTRL`]
+    fn read_tx_control(&mut self, buf_idx: TxBuffer) -> Result<TXB0CTRL, SPI::Error> {
+        let mut ctrl = [0];
+        self.read_registers(TXBnCTRL(buf_idx).address(), &mut ctrl)?;
+        Ok(TXB0CTRL::from_bytes(ctrl))
     }
 }
 
-impl<SPI: SpiDevice> embedded_can::blocking::Can for MCP25xx<SPI> {
+impl<SPI: SpiDevice, V: ChipVariant> embedded_can::blocking::Can for MCP25xx<SPI, V> {
     type Frame = CanFrame;
-    type Error = SpiError<SPI::Error>;
+    type Error = Error<SPI::Error>;
 
     fn transmit(&mut self, frame: &Self::Frame) -> Result<(), Self::Error> {
         let mut replaced_frame;
@@ -253,7 +1085,7 @@ impl<SPI: SpiDevice> embedded_can::blocking::Can for MCP25xx<SPI> {
     }
 }
 
-impl<SPI: SpiDevice> MCP25xx<SPI> {
+impl<SPI: SpiDevice, V: ChipVariant> MCP25xx<SPI, V> {
     /// Read a single register
     pub fn read_register<R: Register>(&mut self) -> Result<R, SPI::Error> {
         let mut reg = [0];
@@ -278,8 +1110,12 @@ impl<SPI: SpiDevice> MCP25xx<SPI> {
         reg: R,
         mask: u8,
     ) -> Result<(), SPI::Error> {
+        self.bit_modify(R::ADDRESS, mask, reg.into())
+    }
+
+    pub(crate) fn bit_modify(&mut self, address: u8, mask: u8, data: u8) -> Result<(), SPI::Error> {
         self.spi
-            .write(&[Instruction::BitModify as u8, R::ADDRESS, mask, reg.into()])
+            .write(&[Instruction::BitModify as u8, address, mask, data])
     }
 
     /// Read multiple consecutive registers
@@ -305,13 +1141,15 @@ impl<SPI: SpiDevice> MCP25xx<SPI> {
     }
 
     /// Set up the selected transmit buffer with CAN frame data
-    #[cfg(any(feature = "mcp2515", feature = "mcp25625"))]
     pub fn load_tx_buffer(
         &mut self,
         buf_idx: TxBuffer,
         frame: &CanFrame,
     ) -> Result<(), SPI::Error> {
         let data = &frame.as_bytes()[0..5 + frame.dlc()];
+        if !self.variant.has_buffer_instructions() {
+            return self.write_registers(TXBnFRAME(buf_idx).address(), data);
+        }
 
         self.spi.transaction(&mut [
             Operation::Write(&[Instruction::LoadTxBuffer as u8 | (buf_idx as u8 * 2)]),
@@ -319,42 +1157,37 @@ impl<SPI: SpiDevice> MCP25xx<SPI> {
         ])
     }
 
-    /// Set up the selected transmit buffer with CAN frame data
-    #[cfg(not(any(feature = "mcp2515", feature = "mcp25625")))]
-    #[inline]
-    pub fn load_tx_buffer(
-        &mut self,
-        buf_idx: TxBuffer,
-        frame: &CanFrame,
-    ) -> Result<(), SPI::Error> {
-        let data = &frame.as_bytes()[0..5 + frame.dlc()];
-        self.write_registers(0x31 + 0x10 * buf_idx as u8, data)
+    /// Read CAN frame data back from the selected transmit buffer
+    pub fn read_tx_buffer(&mut self, buf_idx: TxBuffer) -> Result<CanFrame, SPI::Error> {
+        let mut bytes = [0; TXBnFRAME::LEN];
+        self.read_registers(TXBnFRAME(buf_idx).address(), &mut bytes)?;
+        Ok(CanFrame::from_bytes(bytes))
     }
 
     /// Read CAN frame data from the selected receive buffer
     pub fn read_rx_buffer(&mut self, buf_idx: RxBuffer) -> Result<CanFrame, SPI::Error> {
-        let mut bytes = [0; 13];
+        let mut bytes = [0; RXBnFRAME::LEN];
         self.read_rx(buf_idx, &mut bytes)?;
         let frame = CanFrame::from_bytes(bytes);
 
-        #[cfg(not(any(feature = "mcp2515", feature = "mcp25625")))]
-        // need to manually reset the interrupt flag bit if Instruction::ReadRxBuffer is not available
-        self.modify_register(CANINTF::new(), 1 << buf_idx as u8)?;
+        if !self.variant.has_buffer_instructions() {
+            // need to manually reset the interrupt flag bit if Instruction::ReadRxBuffer is not available
+            self.modify_register(CANINTF::new(), 1 << buf_idx as u8)?;
+        }
         Ok(frame)
     }
 
-    #[cfg(any(feature = "mcp2515", feature = "mcp25625"))]
-    fn read_rx(&mut self, buf_idx: RxBuffer, bytes: &mut [u8; 13]) -> Result<(), SPI::Error> {
-        self.spi.transaction(&mut [
-            Operation::Write(&[Instruction::ReadRxBuffer as u8 | (buf_idx as u8 * 2)]),
-            Operation::Read(bytes),
-        ])
-    }
+    fn read_rx(
+        &mut self,
+        buf_idx: RxBuffer,
+        bytes: &mut [u8; RXBnFRAME::LEN],
+    ) -> Result<(), SPI::Error> {
+        if !self.variant.has_buffer_instructions() {
+            return self.read_registers(RXBnFRAME(buf_idx).address(), bytes);
+        }
 
-    #[cfg(not(any(feature = "mcp2515", feature = "mcp25625")))]
-    fn read_rx(&mut self, buf_idx: RxBuffer, bytes: &mut [u8; 13]) -> Result<(), SPI::Error> {
         self.spi.transaction(&mut [
-            Operation::Write(&[Instruction::Read as u8, 0x61 + 0x10 * buf_idx as u8]),
+            Operation::Write(&[Instruction::ReadRxBuffer as u8 | (buf_idx as u8 * 2)]),
             Operation::Read(bytes),
         ])
     }
@@ -392,6 +1225,90 @@ pub enum TxBuffer {
     TXB2 = 2,
 }
 
+impl TxBuffer {
+    pub(crate) const ALL: [TxBuffer; 3] = [TxBuffer::TXB0, TxBuffer::TXB1, TxBuffer::TXB2];
+}
+
+/// Time the controller needs after a reset before it accepts register writes
+///
+/// The oscillator start-up timer holds the controller in reset for 128 oscillator cycles
+/// after the oscillator started, which can take a few hundred microseconds for a crystal on a cold boot.
+pub(crate) const RESET_DELAY_US: u32 = 2000;
+/// [`CANSTAT`] and [`CANCTRL`] after a reset
+pub(crate) const RESET_VALUES: [u8; 2] = [0b1000_0000, 0b1000_0111];
+
+/// Time between two reads of [`CANSTAT`] or TXBnCTRL while waiting for a mode change or an abort
+pub(crate) const POLL_INTERVAL_US: u32 = 100;
+
+/// Number of reads after the first one within `timeout_ms`
+pub(crate) const fn polls(timeout_ms: u32) -> u32 {
+    timeout_ms.saturating_mul(1000).div_ceil(POLL_INTERVAL_US)
+}
+
+/// The controller has no pending transmissions after a reset, so the final mode is entered quickly
+///
+/// Mode changes which may wait for a frame on the bus take the timeout from the caller.
+pub(crate) const APPLY_CONFIG_TIMEOUT_MS: u32 = 10;
+/// Off the bus, the controller enters Configuration mode without waiting for a frame to end,
+/// so [`CANSTAT`] is only read this many more times, without delay
+pub(crate) const BUS_OFF_MODE_POLLS: u32 = 10;
+
+/// Aborts without a timeout of the caller read TXBnCTRL this many more times, without delay
+///
+/// A read takes at least 2.4 µs at the maximum SPI clock of 10 MHz, which covers the longest frame
+/// at 10 kbps, so only a controller which stopped responding runs out of reads.
+pub(crate) const ABORT_POLLS: u32 = 10_000;
+
+/// Delay of waits which poll the controller without a caller's delay,
+/// see [`BUS_OFF_MODE_POLLS`] and [`ABORT_POLLS`]
+pub(crate) struct NoDelay;
+
+impl DelayNs for NoDelay {
+    fn delay_ns(&mut self, _ns: u32) {}
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::delay::DelayNs for NoDelay {
+    async fn delay_ns(&mut self, _ns: u32) {}
+}
+
+/// `osm` inside the [`CANCTRL`] register
+pub(crate) const ONE_SHOT_MODE: u8 = 0b0000_1000;
+/// `abat` inside the [`CANCTRL`] register
+pub(crate) const ABORT_ALL: u8 = 0b0001_0000;
+
+/// `txreq` inside the TXBnCTRL registers
+pub(crate) const TXREQ: u8 = 0b0000_1000;
+
+/// `errif` inside the [`CANINTF`] register
+pub(crate) const ERROR_FLAG: u8 = 0b0010_0000;
+/// `merrf` inside the [`CANINTF`] register
+pub(crate) const MESSAGE_ERROR_FLAG: u8 = 0b1000_0000;
+/// `rx1ovr` and `rx0ovr` inside the [`EFLG`] register
+pub(crate) const OVERFLOW_FLAGS: u8 = 0b1100_0000;
+/// `merrf`, `rx1if` and `rx0if` inside the [`CANINTF`] register
+pub(crate) const BITRATE_DETECTION_FLAGS: u8 = MESSAGE_ERROR_FLAG | 0b0000_0011;
+
+/// Receive buffer which overflowed and its overflow flag inside the [`EFLG`] register
+pub(crate) fn overflowed_rx_buffer(eflg: EFLG) -> Option<(RxBuffer, u8)> {
+    if eflg.rx0ovr() {
+        Some((RxBuffer::RXB0, 0b0100_0000))
+    } else if eflg.rx1ovr() {
+        Some((RxBuffer::RXB1, 0b1000_0000))
+    } else {
+        None
+    }
+}
+
+/// First transmit buffer with `txreq` set
+pub(crate) fn pending_tx_buffer(status: &ReadStatusResponse) -> Option<TxBuffer> {
+    let pending = [status.txreq0(), status.txreq1(), status.txreq2()];
+    TxBuffer::ALL
+        .into_iter()
+        .zip(pending)
+        .find_map(|(buf_idx, txreq)| txreq.then_some(buf_idx))
+}
+
 /// Receive buffer
 #[derive(Copy, Clone, Debug)]
 pub enum RxBuffer {
@@ -424,24 +1341,24 @@ pub enum Instruction {
     /// Registers that can be modified with this command implement [`Modify`].
     BitModify = 0b0000_0101,
 
-    #[cfg(any(feature = "mcp2515", feature = "mcp25625"))]
-    #[cfg_attr(doc, doc(cfg(any(feature = "mcp2515", feature = "mcp25625"))))]
     /// Quick polling command that indicates a filter match and message type
     /// (standard, extended and/or remote) of the received message.
+    ///
+    /// Note: Not supported by the MCP2510
     RxStatus = 0b1011_0000,
-    #[cfg(any(feature = "mcp2515", feature = "mcp25625"))]
-    #[cfg_attr(doc, doc(cfg(any(feature = "mcp2515", feature = "mcp25625"))))]
     /// When reading a receive buffer, reduces the overhead of a normal `Read`
     /// command by placing the Address Pointer at one of four locations, as
     /// indicated by ‘nm’ in `0b1001_0nm0`.
     ///
     /// Note: The associated RX flag bit (`rxNif` bits in the [`CANINTF`] register) will be cleared after bringing CS high.
+    ///
+    /// Note: Not supported by the MCP2510
     ReadRxBuffer = 0b1001_0000,
-    #[cfg(any(feature = "mcp2515", feature = "mcp25625"))]
-    #[cfg_attr(doc, doc(cfg(any(feature = "mcp2515", feature = "mcp25625"))))]
     /// When loading a transmit buffer, reduces the overhead of a normal `Write`
     /// command by placing the Address Pointer at one of six locations, as
     /// indicated by ‘abc’ in `0b0100_0abc`.
+    ///
+    /// Note: Not supported by the MCP2510
     LoadTxBuffer = 0b0100_0000,
 }
 
@@ -2,12 +2,22 @@
 
 use modular_bitfield::prelude::*;
 
+use crate::{AcceptanceFilter, RxBuffer, TxBuffer};
+
 /// 8 bit Register
 pub trait Register: From<u8> + Into<u8> {
     /// Address of the register
     const ADDRESS: u8;
 }
 
+/// Consecutive registers which are read or written together
+pub trait RegisterBlock {
+    /// Number of registers
+    const LEN: usize;
+    /// Address of the first register
+    fn address(&self) -> u8;
+}
+
 /// Marker trait for Registers that support the `Modify` instruction.
 pub trait Modify {}
 
@@ -56,54 +66,20 @@ pub struct RXB1CTRL {
 }
 
 /// Receive Buffer Operating Mode
-#[cfg(not(any(feature = "mcp2515", feature = "mcp25625")))]
 #[derive(Specifier, Copy, Clone, Debug)]
 #[bits = 2]
 pub enum RXM {
     /// Receive all valid messages using either standard or extended identifiers that meet filter criteria
     Filter = 0b00,
-    /// Receive only valid messages with standard identifiers that meet filter criteria
-    FilterStandard = 0b01,
-    /// Receive only valid messages with extended identifiers that meet filter criteria
-    FilterExtended = 0b10,
-    /// Turn mask/filters off; receive any message
-    ReceiveAny = 0b11,
-}
-
-/// Receive Buffer Operating Mode
-#[cfg(any(feature = "mcp2515", feature = "mcp25625"))]
-#[derive(BitfieldSpecifier, Copy, Clone, Debug)]
-#[bits = 2]
-pub enum RXM {
-    /// Receive all valid messages using either standard or extended identifiers that meet filter criteria
-    Filter = 0b00,
+    /// Only standard frames on the MCP2510, see [`crate::Config::receive_only`]
     Reserved1 = 0b01,
+    /// Only extended frames on the MCP2510, see [`crate::Config::receive_only`]
     Reserved2 = 0b10,
     /// Turn mask/filters off; receive any message
     ReceiveAny = 0b11,
 }
 
 /// Can Control Register
-#[cfg(any(feature = "mcp2515", feature = "mcp25625"))]
-#[bitfield]
-#[repr(u8)]
-#[derive(Copy, Clone, Debug)]
-pub struct CANCTRL {
-    /// CLKOUT Pin Prescaler
-    pub clkpre: CLKPRE,
-    ///  CLKOUT Pin Enable
-    pub clken: bool,
-    #[cfg_attr(doc, doc(cfg(any(feature = "mcp2515", feature = "mcp25625"))))]
-    /// One-Shot Mode
-    pub osm: bool,
-    /// Abort All Pending Transmissions
-    pub abat: bool,
-    /// Request Operation Mode
-    pub reqop: OperationMode,
-}
-
-/// Can Control Register
-#[cfg(not(any(feature = "mcp2515", feature = "mcp25625")))]
 #[bitfield]
 #[repr(u8)]
 #[derive(Copy, Clone, Debug)]
@@ -112,8 +88,8 @@ pub struct CANCTRL {
     pub clkpre: CLKPRE,
     ///  CLKOUT Pin Enable
     pub clken: bool,
-    #[skip]
-    __: B1,
+    /// One-Shot Mode, see [`crate::Config::one_shot_mode`]
+    pub(crate) osm: bool,
     /// Abort All Pending Transmissions
     pub abat: bool,
     /// Request Operation Mode
@@ -121,7 +97,7 @@ pub struct CANCTRL {
 }
 
 /// Request Operation mode
-#[derive(Specifier, Copy, Clone, Debug)]
+#[derive(Specifier, Copy, Clone, Debug, PartialEq, Eq)]
 #[bits = 3]
 pub enum OperationMode {
     NormalOperation = 0b000,
@@ -148,7 +124,7 @@ pub enum CLKPRE {
 /// CANCTRL {
 ///     clkpre: CLKPRE::SystemClockDiv8,
 ///     clken: true,
-///     osm: false, // field not present on MCP2510
+///     osm: false,
 ///     abat: false,
 ///     reqop: OperationMode::Configuration,
 /// }
@@ -259,7 +235,6 @@ pub struct CNF2 {
 /// Configuration 3 Register
 ///
 /// Note: Write operations require Configuration mode
-#[cfg(any(feature = "mcp2515", feature = "mcp25625"))]
 #[bitfield]
 #[repr(u8)]
 #[derive(Copy, Clone, Debug, Default)]
@@ -271,27 +246,9 @@ pub struct CNF3 {
     __: B3,
     /// Wake-up Filter
     pub wakfil: bool,
-    #[cfg_attr(doc, doc(cfg(any(feature = "mcp2515", feature = "mcp25625"))))]
-    /// Start-of-Frame Signal
-    pub sof: bool,
-}
-
-/// Configuration 3 Register
-///
-/// Note: Write operations require Configuration mode
-#[cfg(not(any(feature = "mcp2515", feature = "mcp25625")))]
-#[bitfield]
-#[repr(u8)]
-#[derive(Copy, Clone, Debug, Default)]
-pub struct CNF3 {
-    /// PS2 Length
-    pub phseg2: B3,
-    #[skip]
-    __: B3,
-    /// Wake-up Filter
-    pub wakfil: bool,
-    #[skip]
-    __: B1,
+    #[skip(getters)]
+    /// Start-of-Frame Signal, see [`crate::Config::start_of_frame_signal`]
+    pub(crate) sof: bool,
 }
 
 /// Data Length Code Register
@@ -526,9 +483,9 @@ pub struct ReadStatusResponse {
     pub tx2if: bool,
 }
 
-/// Read Status Response Bitfield
-#[cfg(any(feature = "mcp2515", feature = "mcp25625"))]
-#[cfg_attr(doc, doc(cfg(any(feature = "mcp2515", feature = "mcp25625"))))]
+/// Rx Status Response Bitfield
+///
+/// Note: Not supported by the MCP2510
 #[bitfield]
 #[repr(u8)]
 #[derive(Copy, Clone, Debug, Default)]
@@ -545,9 +502,7 @@ pub struct RxStatusResponse {
 }
 
 /// The filter that matched the received message
-#[cfg(any(feature = "mcp2515", feature = "mcp25625"))]
-#[cfg_attr(doc, doc(cfg(any(feature = "mcp2515", feature = "mcp25625"))))]
-#[derive(BitfieldSpecifier, Copy, Clone, Debug)]
+#[derive(Specifier, Copy, Clone, Debug)]
 #[bits = 3]
 pub enum FilterMatch {
     RXF0,
@@ -560,6 +515,61 @@ pub enum FilterMatch {
     RXF1Rollover,
 }
 
+/// TXBnCTRL of a transmit buffer, all share the layout of [`TXB0CTRL`]
+#[derive(Copy, Clone, Debug)]
+pub struct TXBnCTRL(pub TxBuffer);
+
+/// TXBnSIDH to TXBnD7: identifier, data length code and data of a transmit buffer
+#[derive(Copy, Clone, Debug)]
+pub struct TXBnFRAME(pub TxBuffer);
+
+/// RXBnCTRL of a receive buffer, see [`RXB0CTRL`] and [`RXB1CTRL`]
+#[derive(Copy, Clone, Debug)]
+pub struct RXBnCTRL(pub RxBuffer);
+
+/// RXBnSIDH to RXBnD7: identifier, data length code and data of a receive buffer
+#[derive(Copy, Clone, Debug)]
+pub struct RXBnFRAME(pub RxBuffer);
+
+impl RegisterBlock for TXBnCTRL {
+    const LEN: usize = 1;
+    fn address(&self) -> u8 {
+        0x30 + 0x10 * self.0 as u8
+    }
+}
+impl RegisterBlock for TXBnFRAME {
+    const LEN: usize = 13;
+    fn address(&self) -> u8 {
+        TXBnCTRL(self.0).address() + 1
+    }
+}
+impl RegisterBlock for RXBnCTRL {
+    const LEN: usize = 1;
+    fn address(&self) -> u8 {
+        0x60 + 0x10 * self.0 as u8
+    }
+}
+impl RegisterBlock for RXBnFRAME {
+    const LEN: usize = 13;
+    fn address(&self) -> u8 {
+        RXBnCTRL(self.0).address() + 1
+    }
+}
+/// RXFnSIDH to RXFnEID0 or RXMnSIDH to RXMnEID0
+impl RegisterBlock for AcceptanceFilter {
+    const LEN: usize = 4;
+    fn address(&self) -> u8 {
+        *self as u8
+    }
+}
+/// CNF3 to CNF1
+impl RegisterBlock for CNF {
+    const LEN: usize = 3;
+    fn address(&self) -> u8 {
+        CNF3::ADDRESS
+    }
+}
+
 impl Register for RXB0CTRL {
     const ADDRESS: u8 = 0x60;
 }
@@ -0,0 +1,36 @@
+use crate::RxBuffer;
+
+/// Keeps track of which receive buffer holds the older frame
+///
+/// Frames arrive in RXB0 first and roll over into RXB1 if RXB0 is still full.
+/// Once RXB0 was read while RXB1 is full, the frame in RXB1 is older than the next frame in RXB0.
+/// See <https://www.microchip.com/forums/tm.aspx?m=620741>
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct RxOrder {
+    rxb1_is_older: bool,
+}
+
+impl RxOrder {
+    /// Select the receive buffer to read next given the `rx0if` and `rx1if` flags
+    pub(crate) fn next(&mut self, rx0if: bool, rx1if: bool) -> Option<RxBuffer> {
+        let buf_idx = match (rx0if, rx1if) {
+            (false, false) => return None,
+            (true, false) => RxBuffer::RXB0,
+            (false, true) => RxBuffer::RXB1,
+            (true, true) if self.rxb1_is_older => RxBuffer::RXB1,
+            (true, true) => RxBuffer::RXB0,
+        };
+        // a frame left in RXB1 is older than anything arriving in RXB0 from now on
+        self.rxb1_is_older = matches!(buf_idx, RxBuffer::RXB0) && rx1if;
+        Some(buf_idx)
+    }
+
+    /// Record `rx1if` read again after RXB0 was freed, if it was clear before
+    ///
+    /// A frame rolling over into RXB1 while RXB0 was read is missing in the flags read before.
+    /// The next frame needs RXB0 to be full before it can roll over, which takes longer
+    /// than reading CANINTF again, so a frame found in RXB1 now is older than RXB0.
+    pub(crate) fn rxb0_freed(&mut self, rx1if: bool) {
+        self.rxb1_is_older = rx1if;
+    }
+}
@@ -0,0 +1,267 @@
+use core::fmt::{self, Debug, Display};
+
+use crate::errorstate::ERROR_REGISTERS_LEN;
+use crate::registers::*;
+use crate::{AcceptanceFilter, CanFrame, ErrorState, IdHeader, RxBuffer, TxBuffer};
+
+/// Number of registers in the address space of the controller
+pub(crate) const REGISTER_COUNT: usize = 128;
+
+const FILTERS: [AcceptanceFilter; 8] = [
+    AcceptanceFilter::Filter0,
+    AcceptanceFilter::Filter1,
+    AcceptanceFilter::Filter2,
+    AcceptanceFilter::Filter3,
+    AcceptanceFilter::Filter4,
+    AcceptanceFilter::Filter5,
+    AcceptanceFilter::Mask0,
+    AcceptanceFilter::Mask1,
+];
+const FILTER_NAMES: [&str; 8] = [
+    "RXF0", "RXF1", "RXF2", "RXF3", "RXF4", "RXF5", "RXM0", "RXM1",
+];
+const ID_FIELDS: [&str; 4] = ["SIDH", "SIDL", "EID8", "EID0"];
+
+/// All registers of the controller, read in one burst, see [`crate::MCP25xx::snapshot`]
+///
+/// `{}` prints every register decoded, `{:x}` prints the registers as compact hex string,
+/// which [`RegisterSnapshot::from_hex`] reads back.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct RegisterSnapshot {
+    pub bytes: [u8; REGISTER_COUNT],
+}
+
+/// Register with different values in two [`RegisterSnapshot`]s
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RegisterChange {
+    pub address: u8,
+    pub before: u8,
+    pub after: u8,
+}
+
+/// Datasheet name of the register at an address, e.g. `TXB1D3`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RegisterName(pub u8);
+
+impl RegisterSnapshot {
+    /// Decode a single register
+    pub fn register<R: Register>(&self) -> R {
+        self.bytes[R::ADDRESS as usize].into()
+    }
+
+    /// Raw bytes of a register block
+    pub fn block<B: RegisterBlock>(&self, block: &B) -> &[u8] {
+        let address = block.address() as usize;
+        &self.bytes[address..address + B::LEN]
+    }
+
+    /// Bit timing configuration
+    pub fn cnf(&self) -> CNF {
+        let mut bytes = [0; 3];
+        bytes.copy_from_slice(self.block(&CNF::default()));
+        CNF::from_bytes(bytes)
+    }
+
+    /// Error counters and flags, see [`crate::MCP25xx::error_state`]
+    pub fn error_state(&self) -> ErrorState {
+        let mut bytes = [0; ERROR_REGISTERS_LEN];
+        let address = TEC::ADDRESS as usize;
+        bytes.copy_from_slice(&self.bytes[address..address + ERROR_REGISTERS_LEN]);
+        ErrorState::from_bytes(bytes)
+    }
+
+    /// All TXBnCTRL registers share the layout of [`TXB0CTRL`]
+    pub fn tx_control(&self, buf_idx: TxBuffer) -> TXB0CTRL {
+        TXB0CTRL::from_bytes([self.bytes[TXBnCTRL(buf_idx).address() as usize]])
+    }
+
+    /// Frame in the registers of the transmit buffer
+    pub fn tx_frame(&self, buf_idx: TxBuffer) -> CanFrame {
+        self.frame(&TXBnFRAME(buf_idx))
+    }
+
+    /// Frame in the registers of the receive buffer
+    pub fn rx_frame(&self, buf_idx: RxBuffer) -> CanFrame {
+        self.frame(&RXBnFRAME(buf_idx))
+    }
+
+    /// Identifier of a filter or mask
+    pub fn filter(&self, filter: AcceptanceFilter) -> IdHeader {
+        let mut bytes = [0; AcceptanceFilter::LEN];
+        bytes.copy_from_slice(self.block(&filter));
+        IdHeader::from_bytes(bytes)
+    }
+
+    /// Read back the `{:x}` encoding, upper or lower case
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        if hex.len() != 2 * REGISTER_COUNT {
+            return None;
+        }
+        let mut bytes = [0; REGISTER_COUNT];
+        for (byte, digits) in bytes.iter_mut().zip(hex.as_bytes().chunks(2)) {
+            let digits = core::str::from_utf8(digits).ok()?;
+            *byte = u8::from_str_radix(digits, 16).ok()?;
+        }
+        Some(RegisterSnapshot { bytes })
+    }
+
+    /// Registers whose value changed from `self` to `other`
+    ///
+    /// The copies of [`CANSTAT`] and [`CANCTRL`] at the end of every row are skipped.
+    pub fn diff<'a>(
+        &'a self,
+        other: &'a RegisterSnapshot,
+    ) -> impl Iterator<Item = RegisterChange> + 'a {
+        (0..REGISTER_COUNT as u8)
+            .filter(|&address| address < 0x10 || address & 0x0F < 0x0E)
+            .filter_map(|address| {
+                let (before, after) = (self.bytes[address as usize], other.bytes[address as usize]);
+                (before != after).then_some(RegisterChange {
+                    address,
+                    before,
+                    after,
+                })
+            })
+    }
+
+    fn frame<B: RegisterBlock>(&self, block: &B) -> CanFrame {
+        let mut bytes = [0; 13];
+        bytes.copy_from_slice(self.block(block));
+        CanFrame::from_bytes(bytes)
+    }
+
+    fn line(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        name: &str,
+        address: u8,
+        len: usize,
+        decoded: &dyn Debug,
+    ) -> fmt::Result {
+        write!(f, "{address:#04x} {name:<10}")?;
+        for byte in &self.bytes[address as usize..address as usize + len] {
+            write!(f, " {byte:02x}")?;
+        }
+        writeln!(f, " {decoded:?}")
+    }
+}
+
+impl Display for RegisterSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        macro_rules! register {
+            ($ty:ident) => {
+                self.line(f, stringify!($ty), $ty::ADDRESS, 1, &self.register::<$ty>())?
+            };
+        }
+
+        register!(CANSTAT);
+        register!(CANCTRL);
+        self.line(f, "CNF", CNF3::ADDRESS, CNF::LEN, &self.cnf())?;
+        register!(CANINTE);
+        register!(CANINTF);
+        register!(EFLG);
+        register!(TEC);
+        register!(REC);
+        register!(BFPCTRL);
+        register!(TXRTSCTRL);
+        let tx_names = [
+            ("TXB0CTRL", "TXB0"),
+            ("TXB1CTRL", "TXB1"),
+            ("TXB2CTRL", "TXB2"),
+        ];
+        for (buf_idx, (ctrl, frame)) in TxBuffer::ALL.into_iter().zip(tx_names) {
+            let address = TXBnCTRL(buf_idx).address();
+            self.line(f, ctrl, address, 1, &self.tx_control(buf_idx))?;
+            let address = TXBnFRAME(buf_idx).address();
+            self.line(f, frame, address, TXBnFRAME::LEN, &self.tx_frame(buf_idx))?;
+        }
+        for (buf_idx, frame) in [(RxBuffer::RXB0, "RXB0"), (RxBuffer::RXB1, "RXB1")] {
+            match buf_idx {
+                RxBuffer::RXB0 => register!(RXB0CTRL),
+                RxBuffer::RXB1 => register!(RXB1CTRL),
+            }
+            let address = RXBnFRAME(buf_idx).address();
+            self.line(f, frame, address, RXBnFRAME::LEN, &self.rx_frame(buf_idx))?;
+        }
+        for (filter, name) in FILTERS.into_iter().zip(FILTER_NAMES) {
+            self.line(
+                f,
+                name,
+                filter.address(),
+                AcceptanceFilter::LEN,
+                &self.filter(filter),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::LowerHex for RegisterSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.bytes
+            .iter()
+            .try_for_each(|byte| write!(f, "{byte:02x}"))
+    }
+}
+
+impl Debug for RegisterSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RegisterSnapshot")
+            .field("bytes", &format_args!("{self:x}"))
+            .finish()
+    }
+}
+
+impl Display for RegisterChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({:#04x}): {:#04x} -> {:#04x}",
+            RegisterName(self.address),
+            self.address,
+            self.before,
+            self.after
+        )
+    }
+}
+
+impl Display for RegisterName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (row, col) = (self.0 >> 4, self.0 & 0x0F);
+        match (row, col) {
+            (0..=7, 0x0E) => f.write_str("CANSTAT"),
+            (0..=7, 0x0F) => f.write_str("CANCTRL"),
+            (0..=1, 0x00..=0x0B) => {
+                let filter = FILTER_NAMES[(row * 3 + col / 4) as usize];
+                write!(f, "{filter}{}", ID_FIELDS[(col % 4) as usize])
+            }
+            (0, 0x0C) => f.write_str("BFPCTRL"),
+            (0, 0x0D) => f.write_str("TXRTSCTRL"),
+            (1, 0x0C) => f.write_str("TEC"),
+            (1, 0x0D) => f.write_str("REC"),
+            (2, 0x00..=0x07) => {
+                let mask = FILTER_NAMES[(6 + col / 4) as usize];
+                write!(f, "{mask}{}", ID_FIELDS[(col % 4) as usize])
+            }
+            (2, 0x08) => f.write_str("CNF3"),
+            (2, 0x09) => f.write_str("CNF2"),
+            (2, 0x0A) => f.write_str("CNF1"),
+            (2, 0x0B) => f.write_str("CANINTE"),
+            (2, 0x0C) => f.write_str("CANINTF"),
+            (2, 0x0D) => f.write_str("EFLG"),
+            (3..=7, _) => {
+                let (buffer, n) = match row {
+                    3..=5 => ("TXB", row - 3),
+                    _ => ("RXB", row - 6),
+                };
+                match col {
+                    0x00 => write!(f, "{buffer}{n}CTRL"),
+                    0x01..=0x04 => write!(f, "{buffer}{n}{}", ID_FIELDS[(col - 1) as usize]),
+                    0x05 => write!(f, "{buffer}{n}DLC"),
+                    _ => write!(f, "{buffer}{n}D{}", col - 6),
+                }
+            }
+            _ => write!(f, "{:#04x}", self.0),
+        }
+    }
+}
@@ -0,0 +1,291 @@
+use embedded_can::{Frame, Id};
+use embedded_hal::spi::SpiDevice;
+
+use crate::registers::{CANINTF, Register, RegisterBlock, TXBnCTRL};
+use crate::variant::{ChipVariant, Variant};
+use crate::{ABORT_POLLS, CanFrame, Error, MCP25xx, NoDelay, TxBuffer};
+
+/// `txp` inside the TXBnCTRL registers
+const TXP: u8 = 0b0000_0011;
+
+/// [`MCP25xx`] with a software transmit queue holding up to `N` frames ordered by CAN ID
+///
+/// [`TxQueue::poll`] keeps the three transmit buffers loaded with the highest priority frames
+/// and assigns `txp` in each TXBnCTRL register to match their CAN ID order.
+/// Pending lower priority frames are aborted and queued again if a higher priority frame arrives.
+///
+/// Call [`TxQueue::poll`] whenever a transmit buffer became empty (`txNif` in [`CANINTF`])
+/// and after queueing frames with [`TxQueue::push`].
+///
+/// ## Note:
+/// The transmit priority of a pending frame can not be changed.
+/// If the new frame does not fit into the order of the pending frames,
+/// all pending frames get aborted and loaded again.
+///
+/// ```
+/// # use mcp25xx::doctesthelper::get_mcp25xx;
+/// use embedded_can::{Frame, StandardId};
+/// use mcp25xx::{CanFrame, TxQueue};
+///
+/// let mut tx_queue: TxQueue<_, 16> = TxQueue::new(get_mcp25xx());
+///
+/// let frame = CanFrame::new(StandardId::new(123).unwrap(), &[1, 2, 3]).unwrap();
+/// tx_queue.push(frame).unwrap();
+///
+/// // interrupt handler or main loop
+/// tx_queue.poll().unwrap();
+/// ```
+pub struct TxQueue<SPI: SpiDevice, const N: usize, V: ChipVariant = Variant> {
+    pub mcp25xx: MCP25xx<SPI, V>,
+    queue: PriorityQueue<N>,
+    pending: [Option<Pending>; 3],
+}
+
+/// Frame loaded into a transmit buffer
+struct Pending {
+    frame: CanFrame,
+    txp: u8,
+}
+
+impl<SPI: SpiDevice, const N: usize, V: ChipVariant> TxQueue<SPI, N, V> {
+    pub fn new(mcp25xx: MCP25xx<SPI, V>) -> Self {
+        TxQueue {
+            mcp25xx,
+            queue: PriorityQueue::new(),
+            pending: [None, None, None],
+        }
+    }
+
+    /// Queue a frame for transmission
+    ///
+    /// Returns the frame if the queue is full.
+    /// The frame is loaded into a transmit buffer by the next call to [`TxQueue::poll`].
+    pub fn push(&mut self, frame: CanFrame) -> Result<(), CanFrame> {
+        if self.len() == N {
+            return Err(frame);
+        }
+        self.queue.insert(frame);
+        Ok(())
+    }
+
+    /// Number of frames which are either queued or pending in a transmit buffer
+    pub fn len(&self) -> usize {
+        self.queue.len + self.pending.iter().flatten().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Free the transmit buffers of sent frames and load the highest priority frames
+    ///
+    /// Returns [`Error::AbortTimeout`] if a pending frame could not be aborted
+    /// because the controller stopped responding.
+    pub fn poll(&mut self) -> Result<(), Error<SPI::Error>> {
+        let status = self.mcp25xx.read_status().map_err(Error::Spi)?;
+        let flags = [
+            (status.txreq0(), status.tx0if()),
+            (status.txreq1(), status.tx1if()),
+            (status.txreq2(), status.tx2if()),
+        ];
+        // `txNif` flags of the CANINTF register
+        let mut sent = 0;
+        for (i, (txreq, txif)) in flags.into_iter().enumerate() {
+            if !txreq {
+                self.pending[i] = None;
+            }
+            if txif {
+                self.mcp25xx.bus_off.sent();
+            }
+            sent |= (txif as u8) << (2 + i);
+        }
+        if sent != 0 {
+            self.mcp25xx
+                .bit_modify(CANINTF::ADDRESS, sent, 0)
+                .map_err(Error::Spi)?;
+        }
+
+        while let Some(id) = self.queue.peek().map(CanFrame::id) {
+            let buf_idx = match self.pending.iter().position(Option::is_none) {
+                Some(buf_idx) => buf_idx,
+                None => {
+                    let (buf_idx, lowest) = self.lowest_pending();
+                    if id >= lowest {
+                        break;
+                    }
+                    self.requeue(buf_idx)?;
+                    buf_idx
+                }
+            };
+
+            let frame = self.queue.pop().unwrap();
+            match self.tx_priority(buf_idx, frame.id()) {
+                Some(txp) => self.load(buf_idx, frame, txp).map_err(Error::Spi)?,
+                None => {
+                    self.queue.reinsert(frame);
+                    // the frame sent first needs to end up in front of the queue
+                    let mut order = [0, 1, 2];
+                    order.sort_unstable_by_key(|&buf_idx| self.send_order(buf_idx));
+                    for buf_idx in order {
+                        if self.pending[buf_idx].is_some() {
+                            self.requeue(buf_idx)?;
+                        }
+                    }
+                    for (buf_idx, txp) in [3, 2, 1].into_iter().enumerate() {
+                        if let Some(frame) = self.queue.pop() {
+                            self.load(buf_idx, frame, txp).map_err(Error::Spi)?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Transmit buffer index and CAN ID of the lowest priority pending frame
+    ///
+    /// Of several pending frames with that CAN ID, the one sent last is returned.
+    fn lowest_pending(&self) -> (usize, Id) {
+        let mut lowest = None;
+        for (buf_idx, pending) in self.pending.iter().enumerate() {
+            if let Some(pending) = pending {
+                let id = pending.frame.id();
+                if lowest.is_none_or(|(i, lowest)| {
+                    id > lowest || (id == lowest && self.send_order(buf_idx) < self.send_order(i))
+                }) {
+                    lowest = Some((buf_idx, id));
+                }
+            }
+        }
+        lowest.unwrap()
+    }
+
+    /// Frames with equal `txp` are sent from the highest transmit buffer first
+    fn send_order(&self, buf_idx: usize) -> Option<(u8, usize)> {
+        self.pending[buf_idx]
+            .as_ref()
+            .map(|pending| (pending.txp, buf_idx))
+    }
+
+    /// Highest `txp` which keeps the transmission order of all pending frames in line with their CAN IDs
+    ///
+    /// The controller sends the frame with the highest `txp` first,
+    /// or the one in the highest transmit buffer if `txp` is equal.
+    fn tx_priority(&self, buf_idx: usize, id: Id) -> Option<u8> {
+        (0..=3).rev().find(|&txp| {
+            self.pending
+                .iter()
+                .enumerate()
+                .all(|(i, pending)| match pending {
+                    None => true,
+                    Some(pending) => {
+                        let sent_first = txp > pending.txp || (txp == pending.txp && buf_idx > i);
+                        sent_first == (id < pending.frame.id())
+                    }
+                })
+        })
+    }
+
+    /// Abort a pending frame and put it back into the queue
+    ///
+    /// The frame is dropped if it got sent in the meantime.
+    fn requeue(&mut self, buf_idx: usize) -> Result<(), Error<SPI::Error>> {
+        let ctrl =
+            self.mcp25xx
+                .abort_tx_buffer(TxBuffer::ALL[buf_idx], &mut NoDelay, ABORT_POLLS)?;
+        if let Some(pending) = self.pending[buf_idx].take()
+            && ctrl.abtf()
+        {
+            self.queue.reinsert(pending.frame);
+        }
+        Ok(())
+    }
+
+    fn load(&mut self, buf_idx: usize, frame: CanFrame, txp: u8) -> Result<(), SPI::Error> {
+        let tx_buffer = TxBuffer::ALL[buf_idx];
+        self.mcp25xx
+            .bit_modify(TXBnCTRL(tx_buffer).address(), TXP, txp)?;
+        self.mcp25xx.load_tx_buffer(tx_buffer, &frame)?;
+        self.mcp25xx.request_to_send(tx_buffer)?;
+        self.pending[buf_idx] = Some(Pending { frame, txp });
+        Ok(())
+    }
+}
+
+impl<SPI: SpiDevice, const N: usize, V: ChipVariant> embedded_can::nb::Can for TxQueue<SPI, N, V> {
+    type Frame = CanFrame;
+    type Error = Error<SPI::Error>;
+
+    /// Queue the frame and load the highest priority frames into the transmit buffers
+    fn transmit(&mut self, frame: &Self::Frame) -> nb::Result<Option<Self::Frame>, Self::Error> {
+        if self.len() == N {
+            self.poll()?;
+        }
+        if self.push(frame.clone()).is_err() {
+            return Err(nb::Error::WouldBlock);
+        }
+        self.poll()?;
+        Ok(None)
+    }
+
+    fn receive(&mut self) -> nb::Result<Self::Frame, Self::Error> {
+        embedded_can::nb::Can::receive(&mut self.mcp25xx)
+    }
+}
+
+/// Fixed capacity queue of frames sorted by CAN ID
+///
+/// Frames are stored from lowest to highest priority, frames with equal CAN IDs leave in FIFO order.
+struct PriorityQueue<const N: usize> {
+    frames: [CanFrame; N],
+    len: usize,
+}
+
+impl<const N: usize> PriorityQueue<N> {
+    fn new() -> Self {
+        PriorityQueue {
+            frames: core::array::from_fn(|_| CanFrame::default()),
+            len: 0,
+        }
+    }
+
+    /// The caller needs to make sure the queue is not full
+    fn insert(&mut self, frame: CanFrame) {
+        let id = frame.id();
+        let pos = self.frames[..self.len]
+            .iter()
+            .position(|f| f.id() <= id)
+            .unwrap_or(self.len);
+        self.insert_at(pos, frame);
+    }
+
+    /// Put back a frame which left the queue before the queued frames with the same CAN ID
+    ///
+    /// The caller needs to make sure the queue is not full
+    fn reinsert(&mut self, frame: CanFrame) {
+        let id = frame.id();
+        let pos = self.frames[..self.len]
+            .iter()
+            .position(|f| f.id() < id)
+            .unwrap_or(self.len);
+        self.insert_at(pos, frame);
+    }
+
+    fn insert_at(&mut self, pos: usize, frame: CanFrame) {
+        self.frames[pos..=self.len].rotate_right(1);
+        self.frames[pos] = frame;
+        self.len += 1;
+    }
+
+    fn peek(&self) -> Option<&CanFrame> {
+        self.frames[..self.len].last()
+    }
+
+    fn pop(&mut self) -> Option<CanFrame> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        Some(core::mem::take(&mut self.frames[self.len]))
+    }
+}
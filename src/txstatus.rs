@@ -0,0 +1,62 @@
+use crate::registers::{CANINTF, TXB0CTRL};
+use crate::{CanFrame, TxBuffer};
+
+/// State of the frame in a transmit buffer, see [`crate::MCP25xx::tx_status`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TxStatus {
+    /// The frame waits for the bus or is being transmitted,
+    /// failed attempts are retried unless one-shot mode is enabled
+    Pending,
+    /// The frame was sent (`txNif` in the CANINTF register)
+    Sent,
+    /// The frame lost arbitration and was not sent (`mloa` in the TXBnCTRL register),
+    /// which only ends the transmission in one-shot mode
+    LostArbitration,
+    /// A bus error ended the transmission without sending the frame
+    /// (`txerr` in the TXBnCTRL register), which only happens in one-shot mode
+    Error,
+    /// The frame was aborted before it was sent (`abtf` in the TXBnCTRL register)
+    Aborted,
+    /// The buffer did not send a frame since the reset, or its `txNif` flag was cleared
+    /// since, e.g. by [`crate::MCP25xx::handle_interrupt`]
+    Idle,
+}
+
+impl TxStatus {
+    /// [`TxStatus::Pending`] while `txreq` is set, `None` if it depends on the `txNif` flag
+    ///
+    /// `mloa` and `txerr` of a pending frame only tell about attempts which are retried.
+    pub(crate) fn from_control(ctrl: TXB0CTRL) -> Option<Self> {
+        ctrl.txreq().then_some(TxStatus::Pending)
+    }
+
+    /// State of a buffer which is no longer pending
+    ///
+    /// `txNif` is checked first, a frame which was sent after losing arbitration
+    /// or a bus error keeps `mloa` or `txerr` set.
+    pub(crate) fn from_flags(ctrl: TXB0CTRL, intf: CANINTF, buf_idx: TxBuffer) -> Self {
+        if u8::from(intf) & (TX0IF << buf_idx as u8) != 0 {
+            TxStatus::Sent
+        } else if ctrl.abtf() {
+            TxStatus::Aborted
+        } else if ctrl.mloa() {
+            TxStatus::LostArbitration
+        } else if ctrl.txerr() {
+            TxStatus::Error
+        } else {
+            TxStatus::Idle
+        }
+    }
+}
+
+/// `tx0if` in the CANINTF register
+const TX0IF: u8 = 0b0000_0100;
+
+/// Frame loaded into a transmit buffer, see [`crate::MCP25xx::transmit_tracked`]
+#[derive(Clone, Debug)]
+pub struct Transmission {
+    /// Transmit buffer holding the frame, pass it to [`crate::MCP25xx::tx_status`]
+    pub buffer: TxBuffer,
+    /// Lower priority frame which was pending in the buffer and got replaced
+    pub replaced: Option<CanFrame>,
+}
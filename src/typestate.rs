@@ -0,0 +1,236 @@
+//! [`MCP25xx`](crate::typestate::MCP25xx) only offers what the controller supports
+//! in its current operation mode. Settings which require Configuration mode can only be written
+//! in [`Configuration`](crate::typestate::Configuration), transitions consume the driver and
+//! return it in the new mode once [`CANSTAT`](crate::registers::CANSTAT) reports it.
+//! Only a [`BusOffRecovery`](crate::BusOffRecovery) policy holding the controller off the bus
+//! changes the mode behind the driver's back,
+//! [`MCP25xx::recover_from_bus_off`](crate::typestate::MCP25xx::recover_from_bus_off)
+//! brings it back to Normal mode.
+//!
+//! ```
+//! # use mcp25xx::doctesthelper::{FakeSPI, NoOpDelay};
+//! use embedded_can::nb::Can;
+//! use embedded_can::{Frame, StandardId};
+//! use mcp25xx::bitrates::clock_16mhz::CNF_500K_BPS;
+//! use mcp25xx::typestate::{MCP25xx, Normal};
+//! use mcp25xx::CanFrame;
+//!
+//! # let spi = FakeSPI::default();
+//! # let mut delay = NoOpDelay;
+//! let mut mcp25xx = MCP25xx::new(mcp25xx::MCP25xx::new(spi), &mut delay).unwrap();
+//! mcp25xx.set_bitrate(CNF_500K_BPS).unwrap();
+//!
+//! let mut mcp25xx = mcp25xx.into_mode::<Normal>(&mut delay, 10).unwrap();
+//! let frame = CanFrame::new(StandardId::new(123).unwrap(), &[1, 2, 3]).unwrap();
+//! mcp25xx.transmit(&frame).unwrap();
+//! ```
+//!
+//! ```compile_fail
+//! # use mcp25xx::doctesthelper::{FakeSPI, NoOpDelay};
+//! # use mcp25xx::bitrates::clock_16mhz::CNF_500K_BPS;
+//! # use mcp25xx::typestate::{MCP25xx, Normal};
+//! # let spi = FakeSPI::default();
+//! # let mut delay = NoOpDelay;
+//! let mcp25xx = MCP25xx::new(mcp25xx::MCP25xx::new(spi), &mut delay).unwrap();
+//! let mut mcp25xx = mcp25xx.into_mode::<Normal>(&mut delay, 10).unwrap();
+//! mcp25xx.set_bitrate(CNF_500K_BPS); // requires Configuration mode
+//! ```
+
+use core::fmt::{self, Debug};
+use core::marker::PhantomData;
+
+use embedded_can::StandardId;
+use embedded_hal::delay::DelayNs;
+use embedded_hal::spi::SpiDevice;
+
+use crate::filters::FilterBank;
+use crate::registers::{CNF, OperationMode};
+use crate::variant::{ChipVariant, Mcp2515Features, Variant};
+use crate::{AcceptanceFilter, CanFrame, Config, Error, ErrorState, IdHeader, bitrates};
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Operation mode of the controller
+pub trait Mode: sealed::Sealed {
+    const MODE: OperationMode;
+}
+
+/// Modes in which the controller takes part in bus traffic and frames can be transmitted
+pub trait Active: Mode {}
+
+/// Registers can be written, which are read only in all other modes
+pub struct Configuration;
+/// Transmit and receive on the bus
+pub struct Normal;
+/// Receive all frames without acknowledging them, transmitting is not possible
+pub struct ListenOnly;
+/// Transmitted frames are received internally without being sent on the bus
+pub struct Loopback;
+/// Low power mode, the controller wakes up on bus activity
+pub struct Sleep;
+
+macro_rules! mode {
+    ($ty:ident, $mode:ident) => {
+        impl sealed::Sealed for $ty {}
+        impl Mode for $ty {
+            const MODE: OperationMode = OperationMode::$mode;
+        }
+    };
+}
+
+mode!(Configuration, Configuration);
+mode!(Normal, NormalOperation);
+mode!(ListenOnly, ListenOnly);
+mode!(Loopback, Loopback);
+mode!(Sleep, Sleep);
+
+impl Active for Normal {}
+impl Active for Loopback {}
+
+/// [`crate::MCP25xx`] in a known operation mode
+pub struct MCP25xx<SPI: SpiDevice, M: Mode, V: ChipVariant = Variant> {
+    mcp25xx: crate::MCP25xx<SPI, V>,
+    mode: PhantomData<M>,
+}
+
+/// Failed mode transition
+///
+/// The driver is given back without a mode as it is unknown which mode the controller is in.
+pub struct TransitionError<SPI: SpiDevice, V: ChipVariant = Variant> {
+    pub mcp25xx: crate::MCP25xx<SPI, V>,
+    pub error: Error<SPI::Error>,
+}
+
+impl<SPI: SpiDevice, V: ChipVariant> Debug for TransitionError<SPI, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TransitionError")
+            .field("error", &self.error)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<SPI: SpiDevice, V: ChipVariant> MCP25xx<SPI, Configuration, V> {
+    /// Reset the controller, which puts it into Configuration mode
+    ///
+    /// See [`crate::MCP25xx::reset_and_wait`].
+    pub fn new(
+        mut mcp25xx: crate::MCP25xx<SPI, V>,
+        delay: &mut impl DelayNs,
+    ) -> Result<Self, TransitionError<SPI, V>> {
+        match mcp25xx.reset_and_wait(delay) {
+            Ok(()) => Ok(MCP25xx {
+                mcp25xx,
+                mode: PhantomData,
+            }),
+            Err(error) => Err(TransitionError { mcp25xx, error }),
+        }
+    }
+
+    /// Write everything of the [`Config`] except [`crate::registers::CANCTRL`]
+    ///
+    /// The operation mode is selected by [`MCP25xx::into_mode`] instead.
+    /// Returns [`Error::InvalidBitTiming`] without writing anything if the CNF is invalid.
+    pub fn configure(&mut self, config: &Config<'_, V>) -> Result<(), Error<SPI::Error>> {
+        bitrates::validate(config.cnf).map_err(Error::InvalidBitTiming)?;
+        self.mcp25xx.write_config(config).map_err(Error::Spi)
+    }
+
+    /// See [`crate::MCP25xx::set_bitrate`]
+    ///
+    /// Returns [`Error::InvalidBitTiming`] without writing anything if the CNF is invalid.
+    pub fn set_bitrate(&mut self, cnf: CNF) -> Result<(), Error<SPI::Error>> {
+        bitrates::validate(cnf).map_err(Error::InvalidBitTiming)?;
+        self.mcp25xx.set_bitrate(cnf).map_err(Error::Spi)
+    }
+
+    /// See [`crate::MCP25xx::set_filter`]
+    pub fn set_filter(&mut self, filter: AcceptanceFilter, id: IdHeader) -> Result<(), SPI::Error> {
+        self.mcp25xx.set_filter(filter, id)
+    }
+
+    /// See [`crate::MCP25xx::set_filter_bank`]
+    pub fn set_filter_bank(&mut self, bank: &impl FilterBank) -> Result<(), SPI::Error> {
+        self.mcp25xx.set_filter_bank(bank)
+    }
+}
+
+impl<SPI: SpiDevice, V: Mcp2515Features> MCP25xx<SPI, Configuration, V> {
+    /// See [`crate::MCP25xx::set_data_byte_filter`]
+    pub fn set_data_byte_filter(
+        &mut self,
+        filter: AcceptanceFilter,
+        id: StandardId,
+        bytes: [u8; 2],
+    ) -> Result<(), SPI::Error> {
+        self.mcp25xx.set_data_byte_filter(filter, id, bytes)
+    }
+}
+
+impl<SPI: SpiDevice, M: Mode, V: ChipVariant> MCP25xx<SPI, M, V> {
+    /// Request another operation mode and wait until the controller entered it
+    ///
+    /// See [`crate::MCP25xx::set_mode_and_wait`].
+    pub fn into_mode<N: Mode>(
+        mut self,
+        delay: &mut impl DelayNs,
+        timeout_ms: u32,
+    ) -> Result<MCP25xx<SPI, N, V>, TransitionError<SPI, V>> {
+        match self.mcp25xx.set_mode_and_wait(N::MODE, delay, timeout_ms) {
+            Ok(()) => Ok(MCP25xx {
+                mcp25xx: self.mcp25xx,
+                mode: PhantomData,
+            }),
+            Err(error) => Err(TransitionError {
+                mcp25xx: self.mcp25xx,
+                error,
+            }),
+        }
+    }
+
+    /// Give back the driver without the operation mode
+    pub fn release(self) -> crate::MCP25xx<SPI, V> {
+        self.mcp25xx
+    }
+
+    /// See [`crate::MCP25xx::error_state`]
+    pub fn error_state(&mut self) -> Result<ErrorState, SPI::Error> {
+        self.mcp25xx.error_state()
+    }
+}
+
+impl<SPI: SpiDevice, V: ChipVariant> MCP25xx<SPI, Normal, V> {
+    /// See [`crate::MCP25xx::recover_from_bus_off`]
+    ///
+    /// A [`BusOffRecovery`](crate::BusOffRecovery) policy holding the controller off the bus
+    /// switches it to Configuration mode, while `transmit` and `receive` return
+    /// [`Error::BusOff`]. This switches it back to NormalOperation.
+    pub fn recover_from_bus_off(
+        &mut self,
+        delay: &mut impl DelayNs,
+        timeout_ms: u32,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.mcp25xx.recover_from_bus_off(delay, timeout_ms)
+    }
+}
+
+impl<SPI: SpiDevice, V: ChipVariant> MCP25xx<SPI, ListenOnly, V> {
+    /// See [`embedded_can::nb::Can::receive`]
+    pub fn receive(&mut self) -> nb::Result<CanFrame, Error<SPI::Error>> {
+        embedded_can::nb::Can::receive(&mut self.mcp25xx)
+    }
+}
+
+impl<SPI: SpiDevice, M: Active, V: ChipVariant> embedded_can::nb::Can for MCP25xx<SPI, M, V> {
+    type Frame = CanFrame;
+    type Error = Error<SPI::Error>;
+
+    fn transmit(&mut self, frame: &Self::Frame) -> nb::Result<Option<Self::Frame>, Self::Error> {
+        embedded_can::nb::Can::transmit(&mut self.mcp25xx, frame)
+    }
+
+    fn receive(&mut self) -> nb::Result<Self::Frame, Self::Error> {
+        embedded_can::nb::Can::receive(&mut self.mcp25xx)
+    }
+}
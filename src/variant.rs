@@ -0,0 +1,143 @@
+//! The driver is generic over the chip variant. Marker types fix it at compile time and only
+//! offer the features the chip supports, drivers for different chips can be used side by side:
+//!
+//! ```
+//! # use mcp25xx::doctesthelper::FakeSPI;
+//! use mcp25xx::MCP25xx;
+//! use mcp25xx::variant::{Mcp2510, Mcp2515};
+//!
+//! # let (spi_a, spi_b) = (FakeSPI::default(), FakeSPI::default());
+//! let mut legacy = MCP25xx::with_variant(spi_a, Mcp2510);
+//! let mut current = MCP25xx::with_variant(spi_b, Mcp2515);
+//!
+//! let rx_status = current.rx_status().unwrap();
+//! ```
+//!
+//! ```compile_fail
+//! # use mcp25xx::doctesthelper::FakeSPI;
+//! # use mcp25xx::MCP25xx;
+//! # use mcp25xx::variant::Mcp2510;
+//! # let spi = FakeSPI::default();
+//! let mut legacy = MCP25xx::with_variant(spi, Mcp2510);
+//! legacy.rx_status(); // the MCP2510 does not support the RxStatus instruction
+//! ```
+//!
+//! ```compile_fail
+//! # use embedded_can::StandardId;
+//! # use mcp25xx::{AcceptanceFilter, Config};
+//! # use mcp25xx::variant::Mcp2510;
+//! // the MCP2510 does not compare data bytes
+//! let config = Config::<Mcp2510>::default()
+//!     .data_byte_filter(AcceptanceFilter::Filter0, StandardId::MAX, [1, 2]);
+//! ```
+//!
+//! [`Variant`] selects the chip at runtime and allows every feature,
+//! the caller needs to make sure the chip supports it.
+
+use core::fmt::Debug;
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// CAN controller chip driven by [`crate::MCP25xx`]
+pub trait ChipVariant: sealed::Sealed + Copy + Debug {
+    /// Whether the `ReadRxBuffer`, `LoadTxBuffer` and `RxStatus` instructions are available
+    fn has_buffer_instructions(self) -> bool;
+}
+
+/// Chips supporting the additions of the MCP2515:
+/// one-shot mode, the start-of-frame signal, the `RxStatus` instruction and data byte filters
+pub trait Mcp2515Features: ChipVariant {}
+
+/// Chips supporting the MCP2510 receive modes which only accept standard or extended frames
+pub trait Mcp2510Features: ChipVariant {}
+
+/// MCP2510
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Mcp2510;
+
+/// MCP2515
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Mcp2515;
+
+/// MCP25625, a MCP2515 with integrated transceiver
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Mcp25625;
+
+/// CAN controller chip selected at runtime
+///
+/// The MCP2515 and MCP25625 share the same controller,
+/// they offer more efficient instructions and registers the MCP2510 does not support.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Variant {
+    /// Only uses instructions every variant supports
+    MCP2510,
+    MCP2515,
+    MCP25625,
+}
+
+impl Default for Variant {
+    /// [`Variant::MCP2510`], unless the deprecated `mcp2515` or `mcp25625` feature selects its chip
+    fn default() -> Self {
+        if cfg!(feature = "mcp25625") {
+            Variant::MCP25625
+        } else if cfg!(feature = "mcp2515") {
+            Variant::MCP2515
+        } else {
+            Variant::MCP2510
+        }
+    }
+}
+
+impl Variant {
+    /// Whether the `ReadRxBuffer`, `LoadTxBuffer` and `RxStatus` instructions are available
+    pub const fn has_buffer_instructions(self) -> bool {
+        !matches!(self, Variant::MCP2510)
+    }
+}
+
+impl sealed::Sealed for Mcp2510 {}
+impl sealed::Sealed for Mcp2515 {}
+impl sealed::Sealed for Mcp25625 {}
+impl sealed::Sealed for Variant {}
+
+impl ChipVariant for Mcp2510 {
+    fn has_buffer_instructions(self) -> bool {
+        false
+    }
+}
+
+impl ChipVariant for Mcp2515 {
+    fn has_buffer_instructions(self) -> bool {
+        true
+    }
+}
+
+impl ChipVariant for Mcp25625 {
+    fn has_buffer_instructions(self) -> bool {
+        true
+    }
+}
+
+impl ChipVariant for Variant {
+    fn has_buffer_instructions(self) -> bool {
+        Variant::has_buffer_instructions(self)
+    }
+}
+
+impl Mcp2510Features for Mcp2510 {}
+impl Mcp2510Features for Variant {}
+
+impl Mcp2515Features for Mcp2515 {}
+impl Mcp2515Features for Mcp25625 {}
+impl Mcp2515Features for Variant {}
+
+/// Frame format accepted by a receive buffer, see [`crate::Config::receive_only`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FrameFormat {
+    /// Receive only valid messages with standard identifiers that meet filter criteria
+    Standard,
+    /// Receive only valid messages with extended identifiers that meet filter criteria
+    Extended,
+}
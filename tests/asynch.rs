@@ -0,0 +1,188 @@
+#![cfg(feature = "async")]
+
+use core::future::Future;
+use core::pin::pin;
+use core::task::{Context, Poll, Waker};
+
+use embedded_hal_mock::eh1::digital::{
+    Mock as PinMock, State as PinState, Transaction as PinTransaction,
+};
+use embedded_hal_mock::eh1::spi::{Mock, Transaction};
+
+use mcp25xx::asynch::{InterruptDriven, MCP25xx};
+use mcp25xx::registers::*;
+use mcp25xx::{CanFrame, Instruction, TxBuffer, Variant};
+
+use embedded_can::{Frame, Id, StandardId};
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+#[test]
+fn test_set_mode() {
+    let bus = Mock::new(&[
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![
+            Instruction::BitModify as u8,
+            CANCTRL::ADDRESS,
+            0b11100000,
+            0b10000000,
+        ]),
+        Transaction::transaction_end(),
+    ]);
+    let mut mock = MCP25xx::new(bus);
+    block_on(mock.set_mode(OperationMode::Configuration)).unwrap();
+    mock.spi.done();
+}
+
+#[test]
+fn test_transmit() {
+    let load_instruction = vec![Instruction::Write as u8, 0x31];
+
+    let bus = Mock::new(&[
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::ReadStatus as u8]),
+        Transaction::read_vec(vec![0]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(load_instruction),
+        Transaction::write_vec(vec![0, 32, 0, 0, 3, 1, 2, 3]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Rts as u8 | 1]),
+        Transaction::transaction_end(),
+    ]);
+    let mut mock = MCP25xx::with_variant(bus, Variant::MCP2510);
+
+    let frame = CanFrame::new(Id::Standard(StandardId::new(1).unwrap()), &[1, 2, 3]).unwrap();
+
+    block_on(mock.try_transmit(&frame)).unwrap().unwrap();
+    mock.spi.done();
+}
+
+#[test]
+fn test_interrupt_driven_receive() {
+    let read_instruction = vec![Instruction::Read as u8, 0x71];
+
+    let mut transactions = vec![
+        // transmit buffer 0 empty
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Read as u8, CANSTAT::ADDRESS]),
+        Transaction::read_vec(vec![0b1000_0110]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![
+            Instruction::BitModify as u8,
+            CANINTF::ADDRESS,
+            0b0000_0100,
+            0,
+        ]),
+        Transaction::transaction_end(),
+        // receive buffer 1 full
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Read as u8, CANSTAT::ADDRESS]),
+        Transaction::read_vec(vec![0b1000_1110]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Read as u8, CANINTF::ADDRESS]),
+        Transaction::read_vec(vec![0b10]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(read_instruction),
+        Transaction::read_vec(vec![0, 32, 0, 0, 3, 1, 2, 3, 0, 0, 0, 0, 0]),
+        Transaction::transaction_end(),
+    ];
+    transactions.extend([
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::BitModify as u8, CANINTF::ADDRESS, 2, 0]),
+        Transaction::transaction_end(),
+    ]);
+    let bus = Mock::new(&transactions);
+    let int = PinMock::new(&[
+        PinTransaction::wait_for_state(PinState::Low),
+        PinTransaction::wait_for_state(PinState::Low),
+    ]);
+    let mut mock = InterruptDriven {
+        mcp25xx: MCP25xx::with_variant(bus, Variant::MCP2510),
+        int,
+    };
+
+    let frame = block_on(mock.receive()).unwrap();
+    assert_eq!(frame.id(), Id::Standard(StandardId::new(1).unwrap()));
+    assert_eq!(frame.data(), &[1, 2, 3]);
+
+    mock.mcp25xx.spi.done();
+    mock.int.done();
+}
+
+#[test]
+fn test_interrupt_driven_transmit() {
+    let read_status = |status| {
+        [
+            Transaction::transaction_start(),
+            Transaction::write_vec(vec![Instruction::ReadStatus as u8]),
+            Transaction::read_vec(vec![status]),
+            Transaction::transaction_end(),
+        ]
+    };
+    // all transmit buffers hold frames with CAN ID 0
+    let pending = [0x30, 0x40, 0x50].into_iter().flat_map(|address| {
+        [
+            Transaction::transaction_start(),
+            Transaction::write_vec(vec![Instruction::Read as u8, address]),
+            Transaction::read_vec(vec![0b0000_1000, 0, 0, 0, 0]),
+            Transaction::transaction_end(),
+        ]
+    });
+    let transactions = [
+        read_status(0b0101_0100).to_vec(),
+        pending.collect(),
+        // transmit buffer 0 empty
+        vec![
+            Transaction::transaction_start(),
+            Transaction::write_vec(vec![Instruction::Read as u8, CANSTAT::ADDRESS]),
+            Transaction::read_vec(vec![0b0000_0110]),
+            Transaction::transaction_end(),
+            Transaction::transaction_start(),
+            Transaction::write_vec(vec![
+                Instruction::BitModify as u8,
+                CANINTF::ADDRESS,
+                0b0000_0100,
+                0,
+            ]),
+            Transaction::transaction_end(),
+        ],
+        read_status(0b0101_0000).to_vec(),
+        vec![
+            Transaction::transaction_start(),
+            Transaction::write_vec(vec![Instruction::Write as u8, 0x31]),
+            Transaction::write_vec(vec![0, 32, 0, 0, 0]),
+            Transaction::transaction_end(),
+            Transaction::transaction_start(),
+            Transaction::write_vec(vec![Instruction::Rts as u8 | 1]),
+            Transaction::transaction_end(),
+        ],
+    ]
+    .concat();
+    let bus = Mock::new(&transactions);
+    let int = PinMock::new(&[PinTransaction::wait_for_state(PinState::Low)]);
+    let mut mock = InterruptDriven {
+        mcp25xx: MCP25xx::with_variant(bus, Variant::MCP2510),
+        int,
+    };
+
+    let frame = CanFrame::new(StandardId::new(1).unwrap(), &[]).unwrap();
+    let transmission = block_on(mock.transmit(&frame)).unwrap();
+    assert!(matches!(transmission.buffer, TxBuffer::TXB0));
+    assert!(transmission.replaced.is_none());
+
+    mock.mcp25xx.spi.done();
+    mock.int.done();
+}
@@ -1,87 +1,166 @@
 use mcp25xx::bitrates::*;
 use mcp25xx::registers::CNF;
 
-const CNF8: [CNF; 13] = [
-    // clock_8mhz::CNF_1000K_BPS,
-    clock_8mhz::CNF_500K_BPS,
-    clock_8mhz::CNF_250K_BPS,
-    clock_8mhz::CNF_200K_BPS,
-    clock_8mhz::CNF_125K_BPS,
-    clock_8mhz::CNF_100K_BPS,
-    clock_8mhz::CNF_80K_BPS,
-    clock_8mhz::CNF_50K_BPS,
-    clock_8mhz::CNF_40K_BPS,
-    clock_8mhz::CNF_33K3_BPS,
-    clock_8mhz::CNF_31K25_BPS,
-    clock_8mhz::CNF_20K_BPS,
-    clock_8mhz::CNF_10K_BPS,
-    clock_8mhz::CNF_5K_BPS,
+const CNF8: [(u32, CNF); 13] = [
+    (500_000, clock_8mhz::CNF_500K_BPS),
+    (250_000, clock_8mhz::CNF_250K_BPS),
+    (200_000, clock_8mhz::CNF_200K_BPS),
+    (125_000, clock_8mhz::CNF_125K_BPS),
+    (100_000, clock_8mhz::CNF_100K_BPS),
+    (80_000, clock_8mhz::CNF_80K_BPS),
+    (50_000, clock_8mhz::CNF_50K_BPS),
+    (40_000, clock_8mhz::CNF_40K_BPS),
+    (33_333, clock_8mhz::CNF_33K3_BPS),
+    (31_250, clock_8mhz::CNF_31K25_BPS),
+    (20_000, clock_8mhz::CNF_20K_BPS),
+    (10_000, clock_8mhz::CNF_10K_BPS),
+    (5_000, clock_8mhz::CNF_5K_BPS),
 ];
-const CNF16: [CNF; 14] = [
-    clock_16mhz::CNF_1000K_BPS,
-    clock_16mhz::CNF_500K_BPS,
-    clock_16mhz::CNF_250K_BPS,
-    clock_16mhz::CNF_200K_BPS,
-    clock_16mhz::CNF_125K_BPS,
-    clock_16mhz::CNF_100K_BPS,
-    clock_16mhz::CNF_80K_BPS,
-    clock_16mhz::CNF_83K3_BPS,
-    clock_16mhz::CNF_50K_BPS,
-    clock_16mhz::CNF_40K_BPS,
-    clock_16mhz::CNF_33K3_BPS,
-    clock_16mhz::CNF_20K_BPS,
-    clock_16mhz::CNF_10K_BPS,
-    clock_16mhz::CNF_5K_BPS,
+const CNF16: [(u32, CNF); 14] = [
+    (1_000_000, clock_16mhz::CNF_1000K_BPS),
+    (500_000, clock_16mhz::CNF_500K_BPS),
+    (250_000, clock_16mhz::CNF_250K_BPS),
+    (200_000, clock_16mhz::CNF_200K_BPS),
+    (125_000, clock_16mhz::CNF_125K_BPS),
+    (100_000, clock_16mhz::CNF_100K_BPS),
+    (80_000, clock_16mhz::CNF_80K_BPS),
+    (83_333, clock_16mhz::CNF_83K3_BPS),
+    (50_000, clock_16mhz::CNF_50K_BPS),
+    (40_000, clock_16mhz::CNF_40K_BPS),
+    (33_333, clock_16mhz::CNF_33K3_BPS),
+    (20_000, clock_16mhz::CNF_20K_BPS),
+    (10_000, clock_16mhz::CNF_10K_BPS),
+    (5_000, clock_16mhz::CNF_5K_BPS),
 ];
-const CNF20: [CNF; 11] = [
-    clock_20mhz::CNF_1000K_BPS,
-    clock_20mhz::CNF_500K_BPS,
-    clock_20mhz::CNF_250K_BPS,
-    clock_20mhz::CNF_200K_BPS,
-    clock_20mhz::CNF_125K_BPS,
-    clock_20mhz::CNF_100K_BPS,
-    clock_20mhz::CNF_83K3_BPS,
-    clock_20mhz::CNF_80K_BPS,
-    clock_20mhz::CNF_50K_BPS,
-    clock_20mhz::CNF_40K_BPS,
-    clock_20mhz::CNF_33K3_BPS,
+const CNF20: [(u32, CNF); 11] = [
+    (1_000_000, clock_20mhz::CNF_1000K_BPS),
+    (500_000, clock_20mhz::CNF_500K_BPS),
+    (250_000, clock_20mhz::CNF_250K_BPS),
+    (200_000, clock_20mhz::CNF_200K_BPS),
+    (125_000, clock_20mhz::CNF_125K_BPS),
+    (100_000, clock_20mhz::CNF_100K_BPS),
+    (83_333, clock_20mhz::CNF_83K3_BPS),
+    (80_000, clock_20mhz::CNF_80K_BPS),
+    (50_000, clock_20mhz::CNF_50K_BPS),
+    (40_000, clock_20mhz::CNF_40K_BPS),
+    (33_333, clock_20mhz::CNF_33K3_BPS),
+];
+const CNF10: [(u32, CNF); 10] = [
+    (500_000, clock_10mhz::CNF_500K_BPS),
+    (250_000, clock_10mhz::CNF_250K_BPS),
+    (200_000, clock_10mhz::CNF_200K_BPS),
+    (125_000, clock_10mhz::CNF_125K_BPS),
+    (100_000, clock_10mhz::CNF_100K_BPS),
+    (50_000, clock_10mhz::CNF_50K_BPS),
+    (40_000, clock_10mhz::CNF_40K_BPS),
+    (20_000, clock_10mhz::CNF_20K_BPS),
+    (10_000, clock_10mhz::CNF_10K_BPS),
+    (5_000, clock_10mhz::CNF_5K_BPS),
+];
+const CNF12: [(u32, CNF); 11] = [
+    (500_000, clock_12mhz::CNF_500K_BPS),
+    (250_000, clock_12mhz::CNF_250K_BPS),
+    (200_000, clock_12mhz::CNF_200K_BPS),
+    (125_000, clock_12mhz::CNF_125K_BPS),
+    (100_000, clock_12mhz::CNF_100K_BPS),
+    (80_000, clock_12mhz::CNF_80K_BPS),
+    (50_000, clock_12mhz::CNF_50K_BPS),
+    (40_000, clock_12mhz::CNF_40K_BPS),
+    (20_000, clock_12mhz::CNF_20K_BPS),
+    (10_000, clock_12mhz::CNF_10K_BPS),
+    (5_000, clock_12mhz::CNF_5K_BPS),
+];
+const CNF24: [(u32, CNF); 11] = [
+    (1_000_000, clock_24mhz::CNF_1000K_BPS),
+    (500_000, clock_24mhz::CNF_500K_BPS),
+    (250_000, clock_24mhz::CNF_250K_BPS),
+    (200_000, clock_24mhz::CNF_200K_BPS),
+    (125_000, clock_24mhz::CNF_125K_BPS),
+    (100_000, clock_24mhz::CNF_100K_BPS),
+    (80_000, clock_24mhz::CNF_80K_BPS),
+    (50_000, clock_24mhz::CNF_50K_BPS),
+    (40_000, clock_24mhz::CNF_40K_BPS),
+    (20_000, clock_24mhz::CNF_20K_BPS),
+    (10_000, clock_24mhz::CNF_10K_BPS),
+];
+const CNF25: [(u32, CNF); 7] = [
+    (500_000, clock_25mhz::CNF_500K_BPS),
+    (250_000, clock_25mhz::CNF_250K_BPS),
+    (125_000, clock_25mhz::CNF_125K_BPS),
+    (100_000, clock_25mhz::CNF_100K_BPS),
+    (50_000, clock_25mhz::CNF_50K_BPS),
+    (20_000, clock_25mhz::CNF_20K_BPS),
+    (10_000, clock_25mhz::CNF_10K_BPS),
+];
+
+const TABLES: [(u32, &[(u32, CNF)]); 7] = [
+    (8_000_000, &CNF8),
+    (16_000_000, &CNF16),
+    (20_000_000, &CNF20),
+    (10_000_000, &CNF10),
+    (12_000_000, &CNF12),
+    (24_000_000, &CNF24),
+    (25_000_000, &CNF25),
 ];
 
 #[test]
 fn test_print_bitrate() {
-    for cnf in CNF8 {
-        dbg!(cnf);
-    }
-    for cnf in CNF16 {
-        dbg!(cnf);
-    }
-    for cnf in CNF20 {
-        dbg!(cnf);
+    for (clock_hz, table) in TABLES {
+        for &(_, cnf) in table {
+            dbg!(cnf);
+            dbg!(analyze(clock_hz, cnf).unwrap());
+        }
     }
 }
 
 #[test]
 fn test_bitrates() {
-    for cnf in CNF8 {
-        test_bitrate(8_000_000, cnf);
-    }
-    for cnf in CNF16 {
-        test_bitrate(16_000_000, cnf);
-    }
-    for cnf in CNF20 {
-        test_bitrate(20_000_000, cnf);
+    for (clock_hz, table) in TABLES {
+        for &(bitrate, cnf) in table {
+            test_bitrate(clock_hz, bitrate, cnf);
+        }
     }
 }
 
-fn test_bitrate(f: usize, cnf: CNF) {
-    let tq = 2.0 * (cnf.cnf1.brp() as f64 + 1.0) / f as f64;
-    let prseg = cnf.cnf2.prseg() as usize + 1;
-    let phseg1 = cnf.cnf2.phseg1() as usize + 1;
-    let phseg2 = cnf.cnf3.phseg2() as usize + 1;
-    let sjw = cnf.cnf1.sjw() as usize + 1;
-    let tq_num = 1 + prseg + phseg1 + phseg2;
+fn test_bitrate(clock_hz: u32, bitrate: u32, cnf: CNF) {
+    assert!(cnf.cnf2.phseg1() >= cnf.cnf1.sjw());
+
+    let analysis = analyze(clock_hz, cnf).unwrap();
+    // 33.3 kbit/s and 83.3 kbit/s are rounded
+    assert!(
+        analysis.bitrate.abs_diff(bitrate) <= 1,
+        "{clock_hz} Hz: {} instead of {bitrate} bit/s",
+        analysis.bitrate
+    );
+}
+
+#[test]
+fn test_timing_rules() {
+    // SyncSeg 1, PropSeg 2, PS1 3, PS2 2
+    let valid = CNF::from_bytes([0x01, 0x91, 0x00]);
+    assert_eq!(validate(valid), Ok(()));
+    let analysis = analyze(16_000_000, valid).unwrap();
+    assert_eq!(analysis.bitrate, 1_000_000);
+    assert_eq!(analysis.sample_point, 750);
+    // min(2 / (2 * (13 * 8 - 2)), 1 / (20 * 8))
+    assert_eq!(analysis.oscillator_tolerance_ppm, 6250);
 
-    assert!(phseg1 >= sjw);
-    assert!(phseg2 >= sjw);
-    dbg!(1.0 / (tq_num as f64 * tq));
+    assert_eq!(
+        validate(CNF::from_bytes([0x00, 0x9A, 0x00])),
+        Err(TimingViolation::PhaseSegment2TooShort)
+    );
+    assert_eq!(
+        validate(CNF::from_bytes([0x01, 0x91, 0x40])),
+        Err(TimingViolation::SjwTooLong)
+    );
+    assert_eq!(
+        validate(CNF::from_bytes([0x07, 0x80, 0x00])),
+        Err(TimingViolation::PhaseSegment1TooShort)
+    );
+    assert_eq!(
+        validate(CNF::default()),
+        Err(TimingViolation::TimeQuanta(5))
+    );
+    // without btlmode, PS2 is as long as PS1 and PHSEG2 is ignored
+    assert_eq!(validate(CNF::from_bytes([0x00, 0x19, 0x00])), Ok(()));
 }
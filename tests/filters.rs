@@ -0,0 +1,115 @@
+use embedded_can::{ExtendedId, Id, StandardId};
+use mcp25xx::filters::{FilterPlan, IdRange};
+
+fn sid(raw: u16) -> StandardId {
+    StandardId::new(raw).unwrap()
+}
+
+fn eid(raw: u32) -> ExtendedId {
+    ExtendedId::new(raw).unwrap()
+}
+
+fn all_standard_ids() -> impl Iterator<Item = Id> {
+    (0..=0x7FF).map(|raw| Id::Standard(sid(raw)))
+}
+
+/// Every wanted identifier passes and the leaks are exactly the unwanted accepted identifiers
+fn check_standard(plan: &FilterPlan, wanted: &[IdRange]) {
+    let is_wanted = |id| wanted.iter().any(|range| range.contains(id));
+    for id in all_standard_ids().filter(|&id| is_wanted(id)) {
+        assert!(plan.accepts(id), "{id:?} is rejected");
+    }
+    let expected: Vec<Id> = all_standard_ids()
+        .filter(|&id| plan.accepts(id) && !is_wanted(id))
+        .collect();
+    let mut leaks: Vec<Id> = plan
+        .leaks()
+        .filter(|id| matches!(id, Id::Standard(_)))
+        .collect();
+    leaks.sort();
+    assert_eq!(expected, leaks);
+}
+
+#[test]
+fn test_mixed_formats_without_leaks() {
+    let wanted = [
+        IdRange::Standard(sid(0x10F), sid(0x100)),
+        IdRange::Extended(eid(0x18FF_0000), eid(0x18FF_FFFF)),
+    ];
+    let plan = FilterPlan::new(&wanted, &[]);
+
+    assert_eq!(plan.leaks().count(), 0);
+    assert!(!plan.rxb0ctrl.bukt());
+    assert!(plan.accepts(Id::Standard(sid(0x105))));
+    assert!(plan.accepts(Id::Extended(eid(0x18FF_1234))));
+    assert!(!plan.accepts(Id::Standard(sid(0x110))));
+    assert!(!plan.accepts(Id::Extended(eid(0x18FE_FFFF))));
+    check_standard(&plan, &wanted);
+}
+
+#[test]
+fn test_unaligned_range() {
+    // a single mask would have to accept 0x000 to 0x1FF
+    let wanted = [IdRange::Standard(sid(0x0FF), sid(0x100))];
+    let plan = FilterPlan::new(&wanted, &[]);
+
+    assert_eq!(plan.leaks().count(), 0);
+    check_standard(&plan, &wanted);
+}
+
+#[test]
+fn test_more_ids_than_filters() {
+    let wanted: Vec<IdRange> = [0x111, 0x222, 0x444, 0x0F0, 0x00F, 0x700, 0x7FF, 0x555]
+        .map(|raw| IdRange::from(sid(raw)))
+        .to_vec();
+    let plan = FilterPlan::new(&wanted, &[]);
+
+    check_standard(&plan, &wanted);
+    assert_eq!(plan.leaks().count(), 56);
+}
+
+#[test]
+fn test_priority_ids_in_rxb0() {
+    let priority = [IdRange::from(sid(0x010))];
+    let wanted = [
+        IdRange::Standard(sid(0x200), sid(0x27F)),
+        IdRange::from(sid(0x400)),
+    ];
+    let plan = FilterPlan::new(&wanted, &priority);
+
+    assert!(plan.rxb0ctrl.bukt());
+    assert_eq!(plan.rxb0.mask.bits(), 0x1FFC_0000);
+    for filter in plan.rxb0.filters {
+        assert_eq!(filter.id(), Id::Standard(sid(0x010)));
+    }
+    // the mask of RXB1 keeps 6 bits, 0x200 to 0x27F takes up two filters
+    assert!(
+        plan.leaks()
+            .all(|id| matches!(id, Id::Standard(id) if (0x401..=0x47F).contains(&id.as_raw())))
+    );
+    assert_eq!(plan.leaks().count(), 63);
+    check_standard(&plan, &[priority[0], wanted[0], wanted[1]]);
+}
+
+#[test]
+fn test_mixed_formats_in_one_buffer() {
+    // standard frames are compared with the lower 18 bits of the mask on the MCP2515,
+    // so only the upper 11 bits of the extended identifier are filtered
+    let priority = [IdRange::from(sid(0x001))];
+    let wanted = [IdRange::from(sid(0x100)), IdRange::from(eid(0x0123_4567))];
+    let plan = FilterPlan::new(&wanted, &priority);
+
+    assert!(plan.accepts(Id::Extended(eid(0x0120_0000))));
+    assert!(!plan.accepts(Id::Extended(eid(0x0124_0000))));
+    assert_eq!(plan.leaks().count(), (1 << 18) - 1);
+    check_standard(&plan, &[priority[0], wanted[0], wanted[1]]);
+}
+
+#[test]
+fn test_nothing_wanted() {
+    let plan = FilterPlan::new(&[], &[]);
+
+    assert!(all_standard_ids().all(|id| !plan.accepts(id)));
+    let leaks: Vec<Id> = plan.leaks().collect();
+    assert_eq!(leaks, [Id::Extended(eid(0x1FFF_FFFF))]);
+}
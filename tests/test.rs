@@ -1,10 +1,22 @@
+use embedded_hal_mock::eh1::delay::NoopDelay;
+use embedded_hal_mock::eh1::digital::{
+    Mock as PinMock, State as PinState, Transaction as PinTransaction,
+};
 use embedded_hal_mock::eh1::spi::{Mock, Transaction};
 
+use mcp25xx::bitrates::clock_8mhz::CNF_500K_BPS;
+use mcp25xx::filters::{Filter, Mask, Rxb0Filters};
 use mcp25xx::registers::*;
-use mcp25xx::{CanFrame, Instruction, MCP25xx};
+use mcp25xx::typestate;
+use mcp25xx::variant::{FrameFormat, Mcp2510, Mcp2515};
+use mcp25xx::{
+    AcceptanceFilter, BufferedReceiver, BusOffRecovery, BusState, CanFrame, Config, Error,
+    Instruction, InterruptDriven, InterruptEvent, MCP25xx, RegisterMismatch, RegisterName,
+    RegisterSnapshot, RxBuffer, TxBuffer, TxQueue, TxStatus, Variant, VerifyError,
+};
 
 use embedded_can::nb::Can;
-use embedded_can::{Frame, Id, StandardId};
+use embedded_can::{ExtendedId, Frame, Id, StandardId};
 
 #[test]
 fn test_set_mode() {
@@ -18,7 +30,7 @@ fn test_set_mode() {
         ]),
         Transaction::transaction_end(),
     ]);
-    let mut mock = MCP25xx { spi: bus };
+    let mut mock = MCP25xx::new(bus);
     mock.set_mode(OperationMode::Configuration).unwrap();
     mock.spi.done();
 }
@@ -31,18 +43,86 @@ fn test_set_bitrate() {
         Transaction::write_vec(vec![0x82, 0x90, 0x00]),
         Transaction::transaction_end(),
     ]);
-    let mut mock = MCP25xx { spi: bus };
+    let mut mock = MCP25xx::new(bus);
 
     mock.set_bitrate(mcp25xx::bitrates::clock_8mhz::CNF_500K_BPS)
         .unwrap();
     mock.spi.done();
 }
 
+#[test]
+fn test_set_filter_bank() {
+    let write = |address, bytes: [u8; 4]| {
+        [
+            Transaction::transaction_start(),
+            Transaction::write_vec(vec![Instruction::Write as u8, address]),
+            Transaction::write_vec(bytes.to_vec()),
+            Transaction::transaction_end(),
+        ]
+    };
+    let bus = Mock::new(
+        &[
+            write(0x20, [0xFF, 0xE0, 0x00, 0x00]),
+            write(0x00, [0x24, 0x60, 0x00, 0x00]),
+            write(0x04, [0x91, 0xA8, 0x56, 0x78]),
+        ]
+        .concat(),
+    );
+    let mut mock = MCP25xx::new(bus);
+
+    let filters = Rxb0Filters::new(
+        Mask::standard(StandardId::MAX),
+        [
+            Filter::standard(StandardId::new(0x123).unwrap()),
+            Filter::extended(ExtendedId::new(0x1234_5678).unwrap()),
+        ],
+    );
+    assert_eq!(filters.mask.bits(), 0x1FFC_0000);
+    mock.set_filter_bank(&filters).unwrap();
+    mock.spi.done();
+}
+
+#[test]
+fn test_data_byte_filter() {
+    let write = |address, bytes: [u8; 4]| {
+        [
+            Transaction::transaction_start(),
+            Transaction::write_vec(vec![Instruction::Write as u8, address]),
+            Transaction::write_vec(bytes.to_vec()),
+            Transaction::transaction_end(),
+        ]
+    };
+    let bus = Mock::new(
+        &[
+            write(0x24, [0xFF, 0xE0, 0xFF, 0x00]),
+            write(0x08, [0x00, 0x00, 0x00, 0x00]),
+            write(0x10, [0x9A, 0x40, 0x04, 0x05]),
+            write(0x14, [0x00, 0x00, 0x00, 0x00]),
+            write(0x18, [0x00, 0x00, 0x00, 0x00]),
+            write(0x00, [0x9A, 0x40, 0x04, 0x06]),
+        ]
+        .concat(),
+    );
+    let mut mock = MCP25xx::with_variant(bus, Mcp2515);
+
+    let id = StandardId::new(1234).unwrap();
+    let config = Config::<Mcp2515>::default()
+        .data_byte_filter(AcceptanceFilter::Mask1, StandardId::MAX, [0xFF, 0x00])
+        .data_byte_filter(AcceptanceFilter::Filter3, id, [4, 5]);
+    // only the bank of RXB1 is touched
+    assert!(config.rxb0_filters.is_none());
+    let rxb1 = config.rxb1_filters.unwrap();
+    assert_eq!(rxb1.mask.bits(), 0x1FFC_FF00);
+    assert_eq!(rxb1.filters[1].id(), Id::Standard(id));
+
+    mock.set_filter_bank(&rxb1).unwrap();
+    mock.set_data_byte_filter(AcceptanceFilter::Filter0, id, [4, 6])
+        .unwrap();
+    mock.spi.done();
+}
+
 #[test]
 fn test_transmit() {
-    #[cfg(any(feature = "mcp2515", feature = "mcp25625"))]
-    let load_instruction = vec![Instruction::LoadTxBuffer as u8];
-    #[cfg(not(any(feature = "mcp2515", feature = "mcp25625")))]
     let load_instruction = vec![Instruction::Write as u8, 0x31];
 
     let bus = Mock::new(&[
@@ -58,10 +138,1515 @@ fn test_transmit() {
         Transaction::write_vec(vec![Instruction::Rts as u8 | 1]),
         Transaction::transaction_end(),
     ]);
-    let mut mock = MCP25xx { spi: bus };
+    let mut mock = MCP25xx::with_variant(bus, Variant::MCP2510);
 
     let frame = CanFrame::new(Id::Standard(StandardId::new(1).unwrap()), &[1, 2, 3]).unwrap();
 
     mock.transmit(&frame).unwrap();
     mock.spi.done();
 }
+
+#[test]
+fn test_interrupt_driven_receive() {
+    let read_instruction = vec![Instruction::Read as u8, 0x61];
+
+    let mut transactions = vec![
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Read as u8, CANSTAT::ADDRESS]),
+        Transaction::read_vec(vec![0b1000_1100]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Read as u8, CANINTF::ADDRESS]),
+        Transaction::read_vec(vec![0b01]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(read_instruction),
+        Transaction::read_vec(vec![0, 32, 0, 0, 3, 1, 2, 3, 0, 0, 0, 0, 0]),
+        Transaction::transaction_end(),
+    ];
+    transactions.extend([
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::BitModify as u8, CANINTF::ADDRESS, 1, 0]),
+        Transaction::transaction_end(),
+    ]);
+    transactions.extend(read_intf(0));
+    let bus = Mock::new(&transactions);
+    let int = PinMock::new(&[
+        PinTransaction::get(PinState::High),
+        PinTransaction::get(PinState::Low),
+    ]);
+    let mut mock = InterruptDriven {
+        mcp25xx: MCP25xx::with_variant(bus, Variant::MCP2510),
+        int,
+    };
+
+    // no SPI traffic while INT is high
+    assert!(matches!(mock.receive(), Err(nb::Error::WouldBlock)));
+
+    let frame = mock.receive().unwrap();
+    assert_eq!(frame.id(), Id::Standard(StandardId::new(1).unwrap()));
+    assert_eq!(frame.data(), &[1, 2, 3]);
+
+    mock.mcp25xx.spi.done();
+    mock.int.done();
+}
+
+#[test]
+fn test_transmit_replaces_lower_priority_frame() {
+    let load_instruction = vec![Instruction::Write as u8, 0x41];
+
+    let bus = Mock::new(&[
+        // all transmit buffers pending
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::ReadStatus as u8]),
+        Transaction::read_vec(vec![0b0101_0100]),
+        Transaction::transaction_end(),
+        // control registers and ids of the pending frames: 5, 7 and 6
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Read as u8, 0x30]),
+        Transaction::read_vec(vec![0b0000_1000, 0, 5 << 5, 0, 0]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Read as u8, 0x40]),
+        Transaction::read_vec(vec![0b0000_1000, 0, 7 << 5, 0, 0]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Read as u8, 0x50]),
+        Transaction::read_vec(vec![0b0000_1000, 0, 6 << 5, 0, 0]),
+        Transaction::transaction_end(),
+        // abort transmit buffer 1
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::BitModify as u8, 0x40, 0b0000_1000, 0]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Read as u8, 0x40]),
+        Transaction::read_vec(vec![0b0100_0000]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Read as u8, 0x41]),
+        Transaction::read_vec(vec![0, 7 << 5, 0, 0, 1, 9, 0, 0, 0, 0, 0, 0, 0]),
+        Transaction::transaction_end(),
+        // load the new frame
+        Transaction::transaction_start(),
+        Transaction::write_vec(load_instruction),
+        Transaction::write_vec(vec![0, 32, 0, 0, 3, 1, 2, 3]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Rts as u8 | 2]),
+        Transaction::transaction_end(),
+    ]);
+    let mut mock = MCP25xx::with_variant(bus, Variant::MCP2510);
+
+    let frame = CanFrame::new(StandardId::new(1).unwrap(), &[1, 2, 3]).unwrap();
+
+    let replaced = mock.transmit(&frame).unwrap().unwrap();
+    assert_eq!(replaced.id(), Id::Standard(StandardId::new(7).unwrap()));
+    assert_eq!(replaced.data(), &[9]);
+    mock.spi.done();
+}
+
+#[test]
+fn test_transmit_waits_for_replaced_frame() {
+    let transactions = [
+        read_status(0b0101_0100),
+        vec![(0x30, 5), (0x40, 7), (0x50, 6)]
+            .into_iter()
+            .flat_map(|(address, id)| {
+                [
+                    Transaction::transaction_start(),
+                    Transaction::write_vec(vec![Instruction::Read as u8, address]),
+                    Transaction::read_vec(vec![0b0000_1000, 0, id << 5, 0, 0]),
+                    Transaction::transaction_end(),
+                ]
+            })
+            .collect(),
+        // the frame in TXB1 is being transmitted
+        bit_modify(0x40, 0b0000_1000),
+        read_register(0x40, 0b0000_1000),
+        // it was sent in the meantime, nothing is replaced
+        read_register(0x40, 0b0000_0000),
+        load_tx(1, 0, 1)[3..].to_vec(),
+    ]
+    .concat();
+    let mut mock = MCP25xx::with_variant(Mock::new(&transactions), Variant::MCP2510);
+
+    let frame = CanFrame::new(StandardId::new(1).unwrap(), &[]).unwrap();
+
+    assert!(matches!(mock.transmit(&frame), Err(nb::Error::WouldBlock)));
+    let transmission = mock.transmit_tracked(&frame).unwrap();
+    assert!(matches!(transmission.buffer, TxBuffer::TXB1));
+    assert!(transmission.replaced.is_none());
+    mock.spi.done();
+}
+
+#[test]
+fn test_transmit_keeps_higher_priority_frames() {
+    let bus = Mock::new(&[
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::ReadStatus as u8]),
+        Transaction::read_vec(vec![0b0101_0100]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Read as u8, 0x30]),
+        Transaction::read_vec(vec![0b0000_1000, 0, 5 << 5, 0, 0]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Read as u8, 0x40]),
+        Transaction::read_vec(vec![0b0000_1000, 0, 7 << 5, 0, 0]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Read as u8, 0x50]),
+        Transaction::read_vec(vec![0b0000_1000, 0, 6 << 5, 0, 0]),
+        Transaction::transaction_end(),
+    ]);
+    let mut mock = MCP25xx::new(bus);
+
+    let frame = CanFrame::new(StandardId::new(7).unwrap(), &[1, 2, 3]).unwrap();
+
+    assert!(matches!(mock.transmit(&frame), Err(nb::Error::WouldBlock)));
+    mock.spi.done();
+}
+
+#[test]
+fn test_transmit_reports_failed_frame() {
+    let bus = Mock::new(&[
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::ReadStatus as u8]),
+        Transaction::read_vec(vec![0b0101_0100]),
+        Transaction::transaction_end(),
+        // transmit buffer 1 failed
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Read as u8, 0x30]),
+        Transaction::read_vec(vec![0b0000_1000, 0, 5 << 5, 0, 0]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Read as u8, 0x40]),
+        Transaction::read_vec(vec![0b0001_1000, 0, 7 << 5, 0, 0]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Read as u8, 0x50]),
+        Transaction::read_vec(vec![0b0000_1000, 0, 6 << 5, 0, 0]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Read as u8, EFLG::ADDRESS]),
+        Transaction::read_vec(vec![0b0001_0000]),
+        Transaction::transaction_end(),
+    ]);
+    let mut mock = MCP25xx::new(bus);
+
+    let frame = CanFrame::new(StandardId::new(7).unwrap(), &[1, 2, 3]).unwrap();
+
+    let err = mock.transmit(&frame).unwrap_err();
+    assert!(matches!(
+        err,
+        nb::Error::Other(Error::Transmit(TxBuffer::TXB1))
+    ));
+    mock.spi.done();
+}
+
+fn read_rx(buf_idx: u8) -> Vec<Transaction<u8>> {
+    let read_instruction = vec![Instruction::Read as u8, 0x61 + 0x10 * buf_idx];
+
+    let mut transactions = vec![
+        Transaction::transaction_start(),
+        Transaction::write_vec(read_instruction),
+        Transaction::read_vec(vec![0, buf_idx << 5, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]),
+        Transaction::transaction_end(),
+    ];
+    transactions.extend([
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![
+            Instruction::BitModify as u8,
+            CANINTF::ADDRESS,
+            1 << buf_idx,
+            0,
+        ]),
+        Transaction::transaction_end(),
+    ]);
+    transactions
+}
+fn load_tx(buf_idx: u8, txp: u8, id: u8) -> Vec<Transaction<u8>> {
+    let load_instruction = vec![Instruction::Write as u8, 0x31 + 0x10 * buf_idx];
+
+    vec![
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![
+            Instruction::BitModify as u8,
+            0x30 + 0x10 * buf_idx,
+            0b11,
+            txp,
+        ]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(load_instruction),
+        Transaction::write_vec(vec![0, id << 5, 0, 0, 0]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Rts as u8 | (1 << buf_idx)]),
+        Transaction::transaction_end(),
+    ]
+}
+
+fn read_intf(flags: u8) -> Vec<Transaction<u8>> {
+    vec![
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Read as u8, CANINTF::ADDRESS]),
+        Transaction::read_vec(vec![flags]),
+        Transaction::transaction_end(),
+    ]
+}
+
+fn read_status(status: u8) -> Vec<Transaction<u8>> {
+    vec![
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::ReadStatus as u8]),
+        Transaction::read_vec(vec![status]),
+        Transaction::transaction_end(),
+    ]
+}
+
+#[test]
+fn test_receive_in_arrival_order() {
+    // A arrives in RXB0, B rolls over into RXB1, C arrives in RXB0 after A was read
+    let transactions = [
+        read_intf(0b11),
+        read_rx(0),
+        read_intf(0b11),
+        read_rx(1),
+        read_intf(0b01),
+        read_rx(0),
+        read_intf(0),
+    ]
+    .concat();
+    let bus = Mock::new(&transactions);
+    let mut mock = MCP25xx::with_variant(bus, Variant::MCP2510);
+
+    let ids: Vec<_> = (0..3).map(|_| mock.receive().unwrap().id()).collect();
+    assert_eq!(
+        ids,
+        [0, 1, 0].map(|id| Id::Standard(StandardId::new(id).unwrap()))
+    );
+    mock.spi.done();
+}
+
+#[test]
+fn test_receive_rollover_while_reading() {
+    // B rolls over into RXB1 while A is read from RXB0, C arrives in RXB0 afterwards
+    let transactions = [
+        read_intf(0b01),
+        read_rx(0),
+        read_intf(0b10),
+        read_intf(0b11),
+        read_rx(1),
+        read_intf(0b01),
+        read_rx(0),
+        read_intf(0),
+    ]
+    .concat();
+    let bus = Mock::new(&transactions);
+    let mut mock = MCP25xx::with_variant(bus, Variant::MCP2510);
+
+    let ids: Vec<_> = (0..3).map(|_| mock.receive().unwrap().id()).collect();
+    assert_eq!(
+        ids,
+        [0, 1, 0].map(|id| Id::Standard(StandardId::new(id).unwrap()))
+    );
+    mock.spi.done();
+}
+
+#[test]
+fn test_buffered_receiver() {
+    let transactions = [
+        read_intf(0b11),
+        read_rx(0),
+        read_intf(0b10),
+        read_rx(1),
+        // receive buffer 0 overflowed
+        read_intf(0b0010_0000),
+        vec![
+            Transaction::transaction_start(),
+            Transaction::write_vec(vec![Instruction::Read as u8, EFLG::ADDRESS]),
+            Transaction::read_vec(vec![0b0100_0000]),
+            Transaction::transaction_end(),
+            Transaction::transaction_start(),
+            Transaction::write_vec(vec![
+                Instruction::BitModify as u8,
+                EFLG::ADDRESS,
+                0b0100_0000,
+                0,
+            ]),
+            Transaction::transaction_end(),
+            Transaction::transaction_start(),
+            Transaction::write_vec(vec![
+                Instruction::BitModify as u8,
+                CANINTF::ADDRESS,
+                0b0010_0000,
+                0,
+            ]),
+            Transaction::transaction_end(),
+        ],
+        read_intf(0),
+    ]
+    .concat();
+    let bus = Mock::new(&transactions);
+    let mut receiver: BufferedReceiver<_, 1> =
+        BufferedReceiver::new(MCP25xx::with_variant(bus, Variant::MCP2510));
+
+    assert_eq!(receiver.poll().unwrap(), 2);
+    assert_eq!(receiver.len(), 1);
+    assert_eq!(receiver.dropped(), 1);
+    assert_eq!(receiver.overflows(), 1);
+
+    let frame = receiver.receive().unwrap();
+    assert_eq!(frame.id(), Id::Standard(StandardId::new(0).unwrap()));
+    assert!(receiver.is_empty());
+    receiver.mcp25xx.spi.done();
+}
+
+#[test]
+fn test_tx_queue() {
+    let transactions = [
+        // the highest priority frames get loaded first,
+        // with equal txp the highest transmit buffer would be sent first
+        read_status(0),
+        load_tx(0, 3, 1),
+        load_tx(1, 2, 2),
+        load_tx(2, 1, 3),
+        // transmit buffer 0 was sent
+        read_status(0b0101_1000),
+        vec![
+            Transaction::transaction_start(),
+            Transaction::write_vec(vec![
+                Instruction::BitModify as u8,
+                CANINTF::ADDRESS,
+                0b0000_0100,
+                0,
+            ]),
+            Transaction::transaction_end(),
+        ],
+        load_tx(0, 1, 4),
+        // frame 4 gets replaced by frame 0
+        read_status(0b0101_0100),
+        vec![
+            Transaction::transaction_start(),
+            Transaction::write_vec(vec![Instruction::BitModify as u8, 0x30, 0b0000_1000, 0]),
+            Transaction::transaction_end(),
+            Transaction::transaction_start(),
+            Transaction::write_vec(vec![Instruction::Read as u8, 0x30]),
+            Transaction::read_vec(vec![0b0100_0000]),
+            Transaction::transaction_end(),
+        ],
+        load_tx(0, 3, 0),
+    ]
+    .concat();
+    let bus = Mock::new(&transactions);
+    let mut tx_queue: TxQueue<_, 5> = TxQueue::new(MCP25xx::with_variant(bus, Variant::MCP2510));
+
+    let frame = |id| CanFrame::new(StandardId::new(id).unwrap(), &[]).unwrap();
+    for id in [3, 1, 2, 4] {
+        tx_queue.push(frame(id)).unwrap();
+    }
+    tx_queue.poll().unwrap();
+    assert_eq!(tx_queue.len(), 4);
+
+    tx_queue.poll().unwrap();
+    assert_eq!(tx_queue.len(), 3);
+
+    tx_queue.push(frame(0)).unwrap();
+    tx_queue.poll().unwrap();
+    assert_eq!(tx_queue.len(), 4);
+
+    tx_queue.mcp25xx.spi.done();
+}
+
+#[test]
+fn test_tx_queue_keeps_fifo_order() {
+    let load = |buf_idx: u8, txp, n| {
+        let mut transactions = load_tx(buf_idx, txp, 5);
+        transactions[5] = Transaction::write_vec(vec![0, 5 << 5, 0, 0, 1, n]);
+        transactions
+    };
+    let transactions = [
+        read_status(0),
+        load(0, 3, 1),
+        load(1, 2, 2),
+        load(2, 1, 3),
+        // frame 3 gets displaced by a higher priority frame
+        read_status(0b0101_0100),
+        vec![
+            Transaction::transaction_start(),
+            Transaction::write_vec(vec![Instruction::BitModify as u8, 0x50, 0b0000_1000, 0]),
+            Transaction::transaction_end(),
+            Transaction::transaction_start(),
+            Transaction::write_vec(vec![Instruction::Read as u8, 0x50]),
+            Transaction::read_vec(vec![0b0100_0000]),
+            Transaction::transaction_end(),
+        ],
+        load_tx(2, 3, 0),
+        // frame 3 still leaves before frame 4
+        read_status(0b0101_0000),
+        load(0, 2, 3),
+    ]
+    .concat();
+    let bus = Mock::new(&transactions);
+    let mut tx_queue: TxQueue<_, 5> = TxQueue::new(MCP25xx::with_variant(bus, Variant::MCP2510));
+
+    for n in 1..=4 {
+        let frame = CanFrame::new(StandardId::new(5).unwrap(), &[n]).unwrap();
+        tx_queue.push(frame).unwrap();
+    }
+    tx_queue.poll().unwrap();
+    tx_queue
+        .push(CanFrame::new(StandardId::new(0).unwrap(), &[]).unwrap())
+        .unwrap();
+    tx_queue.poll().unwrap();
+    assert_eq!(tx_queue.len(), 5);
+
+    tx_queue.poll().unwrap();
+    assert_eq!(tx_queue.len(), 4);
+
+    tx_queue.mcp25xx.spi.done();
+}
+
+fn read_error_registers(tec: u8, rec: u8, eflg: u8) -> Vec<Transaction<u8>> {
+    let mut bytes = vec![0; 18];
+    bytes[0] = tec;
+    bytes[1] = rec;
+    bytes[17] = eflg;
+    vec![
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Read as u8, TEC::ADDRESS]),
+        Transaction::read_vec(bytes),
+        Transaction::transaction_end(),
+    ]
+}
+
+#[test]
+fn test_error_state_transitions() {
+    let transactions = [
+        read_error_registers(130, 12, 0b0001_0101),
+        read_error_registers(140, 12, 0b0001_0101),
+        read_error_registers(0, 0, 0),
+    ]
+    .concat();
+    let bus = Mock::new(&transactions);
+    let mut mock = MCP25xx::new(bus);
+
+    let transition = mock.poll_error_state().unwrap().unwrap();
+    assert_eq!(transition.from, BusState::ErrorActive);
+    assert_eq!(transition.to.bus_state, BusState::ErrorPassive);
+    assert_eq!(transition.to.tec, TEC(130));
+    assert_eq!(transition.to.rec, REC(12));
+
+    // counters changed, state did not
+    assert!(mock.poll_error_state().unwrap().is_none());
+
+    let transition = mock.poll_error_state().unwrap().unwrap();
+    assert_eq!(transition.from, BusState::ErrorPassive);
+    assert_eq!(transition.to.bus_state, BusState::ErrorActive);
+    mock.spi.done();
+}
+
+fn apply_config<V>(config: &Config<V>) -> Vec<Transaction<u8>> {
+    vec![
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Reset as u8]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Read as u8, CANSTAT::ADDRESS]),
+        Transaction::read_vec(vec![0b1000_0000, 0b1000_0111]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Write as u8, CNF3::ADDRESS]),
+        Transaction::write_vec(config.cnf.into_bytes().to_vec()),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Write as u8, RXB0CTRL::ADDRESS]),
+        Transaction::write_vec(vec![config.rxb0ctrl.into()]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Write as u8, RXB1CTRL::ADDRESS]),
+        Transaction::write_vec(vec![config.rxb1ctrl.into()]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Write as u8, CANINTE::ADDRESS]),
+        Transaction::write_vec(vec![config.caninte.into()]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![
+            Instruction::Write as u8,
+            CANCTRL::ADDRESS,
+            config.canctrl.into(),
+        ]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Read as u8, CANSTAT::ADDRESS]),
+        Transaction::read_vec(vec![u8::from(config.canctrl) & 0b1110_0000]),
+        Transaction::transaction_end(),
+    ]
+}
+
+fn read_eflg(eflg: u8) -> Vec<Transaction<u8>> {
+    vec![
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Read as u8, EFLG::ADDRESS]),
+        Transaction::read_vec(vec![eflg]),
+        Transaction::transaction_end(),
+    ]
+}
+
+fn set_mode(mode: u8) -> Vec<Transaction<u8>> {
+    vec![
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![
+            Instruction::BitModify as u8,
+            CANCTRL::ADDRESS,
+            0b1110_0000,
+            mode << 5,
+        ]),
+        Transaction::transaction_end(),
+    ]
+}
+
+fn abort_tx(buf_idx: u8) -> Vec<Transaction<u8>> {
+    let address = 0x30 + 0x10 * buf_idx;
+    vec![
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::BitModify as u8, address, 0b0000_1000, 0]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Read as u8, address]),
+        Transaction::read_vec(vec![0b0100_0000]),
+        Transaction::transaction_end(),
+    ]
+}
+
+#[test]
+fn test_bus_off_manual_recovery() {
+    let config = Config::default()
+        .mode(OperationMode::NormalOperation)
+        .bitrate(CNF_500K_BPS)
+        .bus_off_recovery(BusOffRecovery::Manual);
+    let transactions = [
+        apply_config(&config),
+        // error interrupt reports bus-off with a frame pending in TXB0
+        read_register(CANSTAT::ADDRESS, 0b0000_0010),
+        read_eflg(0b0010_0000),
+        read_status(0b0000_0100),
+        abort_tx(0),
+        set_mode(0b100),
+        read_canstat(0b100),
+        bit_modify(CANINTF::ADDRESS, 0b0010_0000),
+        // receive only reports the bus-off once the receive buffers are empty
+        read_intf(0),
+        // rejoin
+        set_mode(0b000),
+        read_canstat(0b000),
+        read_status(0),
+        load_tx(0, 0, 1)[3..].to_vec(),
+    ]
+    .concat();
+    let bus = Mock::new(&transactions);
+    let mut mock = MCP25xx::with_variant(bus, Variant::MCP2510);
+    mock.apply_config(&config, &mut NoopDelay).unwrap();
+
+    let frame = CanFrame::new(StandardId::new(1).unwrap(), &[]).unwrap();
+
+    let event = mock.handle_interrupt().unwrap();
+    assert!(matches!(event, Some(InterruptEvent::Error(eflg)) if eflg.txbo()));
+    // held off the bus without talking to the controller
+    let err = mock.transmit(&frame).unwrap_err();
+    assert!(matches!(err, nb::Error::Other(Error::BusOff)));
+    let err = mock.receive().unwrap_err();
+    assert!(matches!(err, nb::Error::Other(Error::BusOff)));
+
+    mock.recover_from_bus_off(&mut NoopDelay, 1).unwrap();
+    mock.transmit(&frame).unwrap();
+    mock.spi.done();
+}
+
+#[test]
+fn test_bus_off_recovery_attempts() {
+    let config = Config::default()
+        .mode(OperationMode::NormalOperation)
+        .bitrate(CNF_500K_BPS)
+        .bus_off_recovery(BusOffRecovery::Attempts(1));
+    let transactions = [
+        apply_config(&config),
+        // first bus-off, seen by receive, the controller recovers on its own
+        read_intf(0b0010_0000),
+        read_eflg(0b0010_0000),
+        read_status(0),
+        read_intf(0b0010_0000),
+        read_eflg(0b0010_0000),
+        read_intf(0b0010_0000),
+        read_eflg(0),
+        // second bus-off, seen by transmit through the failed frame in TXB0
+        read_status(0b0101_0100),
+        vec![
+            (0x30, 0b0001_1000),
+            (0x40, 0b0000_1000),
+            (0x50, 0b0000_1000),
+        ]
+        .into_iter()
+        .flat_map(|(address, ctrl)| {
+            [
+                Transaction::transaction_start(),
+                Transaction::write_vec(vec![Instruction::Read as u8, address]),
+                Transaction::read_vec(vec![ctrl, 0, 0, 0, 0]),
+                Transaction::transaction_end(),
+            ]
+        })
+        .collect(),
+        read_eflg(0b0010_0000),
+        read_status(0b0101_0100),
+        abort_tx(0),
+        abort_tx(1),
+        abort_tx(2),
+        // attempts are used up
+        set_mode(0b100),
+        read_canstat(0b000),
+        read_canstat(0b100),
+    ]
+    .concat();
+    let bus = Mock::new(&transactions);
+    let mut mock = MCP25xx::new(bus);
+    mock.apply_config(&config, &mut NoopDelay).unwrap();
+
+    // pending frames are only aborted when the bus-off is seen first
+    for _ in 0..3 {
+        assert!(matches!(mock.receive(), Err(nb::Error::WouldBlock)));
+    }
+
+    let frame = CanFrame::new(StandardId::new(1).unwrap(), &[]).unwrap();
+    for _ in 0..2 {
+        let err = mock.transmit(&frame).unwrap_err();
+        assert!(matches!(err, nb::Error::Other(Error::BusOff)));
+    }
+    mock.spi.done();
+}
+
+#[test]
+fn test_bus_off_attempts_start_over() {
+    let config = Config::default()
+        .mode(OperationMode::NormalOperation)
+        .bitrate(CNF_500K_BPS)
+        .bus_off_recovery(BusOffRecovery::Attempts(1));
+    let bus_off = [
+        read_intf(0b0010_0000),
+        read_eflg(0b0010_0000),
+        read_status(0),
+        read_intf(0b0010_0000),
+        read_eflg(0),
+    ]
+    .concat();
+    let transmit = [read_status(0), load_tx(0, 0, 1)[3..].to_vec()].concat();
+    let transactions = [
+        apply_config(&config),
+        bus_off.clone(),
+        vec![transmit; 256].concat(),
+        // recovers on its own again
+        bus_off,
+    ]
+    .concat();
+    let bus = Mock::new(&transactions);
+    let mut mock = MCP25xx::with_variant(bus, Variant::MCP2510);
+    mock.apply_config(&config, &mut NoopDelay).unwrap();
+
+    let frame = CanFrame::new(StandardId::new(1).unwrap(), &[]).unwrap();
+    for _ in 0..2 {
+        assert!(matches!(mock.receive(), Err(nb::Error::WouldBlock)));
+    }
+    for _ in 0..256 {
+        mock.transmit(&frame).unwrap();
+    }
+    for _ in 0..2 {
+        assert!(matches!(mock.receive(), Err(nb::Error::WouldBlock)));
+    }
+    mock.spi.done();
+}
+
+fn read_canstat(opmod: u8) -> Vec<Transaction<u8>> {
+    vec![
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Read as u8, CANSTAT::ADDRESS]),
+        Transaction::read_vec(vec![opmod << 5]),
+        Transaction::transaction_end(),
+    ]
+}
+
+#[test]
+fn test_set_mode_and_wait() {
+    let transactions = [
+        // waits for a pending transmission to finish
+        set_mode(0b100),
+        read_canstat(0b000),
+        read_canstat(0b000),
+        read_canstat(0b100),
+        // the controller does not leave NormalOperation
+        set_mode(0b100),
+        read_canstat(0b000),
+        // the controller ended up in Sleep mode
+        set_mode(0b011),
+        read_canstat(0b000),
+        read_canstat(0b001),
+    ]
+    .concat();
+    let bus = Mock::new(&transactions);
+    let mut mock = MCP25xx::new(bus);
+    let mut delay = NoopDelay;
+
+    mock.set_mode_and_wait(OperationMode::Configuration, &mut delay, 1)
+        .unwrap();
+
+    let err = mock
+        .set_mode_and_wait(OperationMode::Configuration, &mut delay, 0)
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        Error::ModeTimeout(OperationMode::NormalOperation)
+    ));
+
+    let err = mock
+        .set_mode_and_wait(OperationMode::ListenOnly, &mut delay, 1)
+        .unwrap_err();
+    assert!(matches!(err, Error::ModeMismatch(OperationMode::Sleep)));
+    mock.spi.done();
+}
+
+#[test]
+fn test_reset_device_not_found() {
+    let bus = Mock::new(&[
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Reset as u8]),
+        Transaction::transaction_end(),
+        // MISO is pulled up without a controller
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Read as u8, CANSTAT::ADDRESS]),
+        Transaction::read_vec(vec![0xFF, 0xFF]),
+        Transaction::transaction_end(),
+    ]);
+    let mut mock = MCP25xx::new(bus);
+
+    let err = mock.reset_and_wait(&mut NoopDelay).unwrap_err();
+    assert!(matches!(err, Error::DeviceNotFound));
+    mock.spi.done();
+}
+
+#[test]
+fn test_buffer_instructions() {
+    let bus = Mock::new(&[
+        // receive buffer 1 full
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Read as u8, CANINTF::ADDRESS]),
+        Transaction::read_vec(vec![0b10]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::ReadRxBuffer as u8 | 2]),
+        Transaction::read_vec(vec![0, 32, 0, 0, 3, 1, 2, 3, 0, 0, 0, 0, 0]),
+        Transaction::transaction_end(),
+        // transmit
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::ReadStatus as u8]),
+        Transaction::read_vec(vec![0]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::LoadTxBuffer as u8]),
+        Transaction::write_vec(vec![0, 32, 0, 0, 3, 1, 2, 3]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Rts as u8 | 1]),
+        Transaction::transaction_end(),
+    ]);
+    let mut mock = MCP25xx::with_variant(bus, Variant::MCP2515);
+
+    let frame = mock.receive().unwrap();
+    assert_eq!(frame.id(), Id::Standard(StandardId::new(1).unwrap()));
+
+    mock.transmit(&frame).unwrap();
+    mock.spi.done();
+}
+
+#[test]
+fn test_detect_variant() {
+    let bus = Mock::new(&[
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Read as u8, CANCTRL::ADDRESS]),
+        Transaction::read_vec(vec![0b1000_0111]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![
+            Instruction::BitModify as u8,
+            CANCTRL::ADDRESS,
+            0b0000_1000,
+            0b0000_1000,
+        ]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Read as u8, CANCTRL::ADDRESS]),
+        Transaction::read_vec(vec![0b1000_1111]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![
+            Instruction::BitModify as u8,
+            CANCTRL::ADDRESS,
+            0b0000_1000,
+            0b1000_0111,
+        ]),
+        Transaction::transaction_end(),
+    ]);
+    let mut mock = MCP25xx::with_variant(bus, Variant::MCP2510);
+    assert_eq!(mock.variant(), Variant::MCP2510);
+
+    assert_eq!(mock.detect_variant().unwrap(), Variant::MCP2515);
+    assert_eq!(mock.variant(), Variant::MCP2515);
+    mock.spi.done();
+}
+
+#[test]
+fn test_variant_markers() {
+    let frame = CanFrame::new(StandardId::new(1).unwrap(), &[1, 2, 3]).unwrap();
+
+    let legacy_config = Config::<Mcp2510>::default()
+        .mode(OperationMode::NormalOperation)
+        .bitrate(CNF_500K_BPS)
+        .receive_only(RxBuffer::RXB1, FrameFormat::Extended);
+    assert_eq!(u8::from(legacy_config.rxb1ctrl), 0b0100_0000);
+    let mut transactions = apply_config(&legacy_config);
+    transactions.extend(read_status(0));
+    transactions.extend([
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Write as u8, 0x31]),
+        Transaction::write_vec(vec![0, 32, 0, 0, 3, 1, 2, 3]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Rts as u8 | 1]),
+        Transaction::transaction_end(),
+    ]);
+    let mut legacy = MCP25xx::with_variant(Mock::new(&transactions), Mcp2510);
+
+    let config = Config::<Mcp2515>::default()
+        .mode(OperationMode::NormalOperation)
+        .bitrate(mcp25xx::bitrates::clock_10mhz::CNF_500K_BPS)
+        .one_shot_mode(true)
+        .start_of_frame_signal(true);
+    assert_eq!(u8::from(config.canctrl), 0b0000_1111);
+    assert_eq!(config.cnf.into_bytes()[0], 0b1000_0001);
+    let mut transactions = apply_config(&config);
+    transactions.extend(read_status(0));
+    transactions.extend([
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::LoadTxBuffer as u8]),
+        Transaction::write_vec(vec![0, 32, 0, 0, 3, 1, 2, 3]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Rts as u8 | 1]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::RxStatus as u8]),
+        Transaction::read_vec(vec![0]),
+        Transaction::transaction_end(),
+    ]);
+    let mut current = MCP25xx::with_variant(Mock::new(&transactions), Mcp2515);
+
+    legacy.apply_config(&legacy_config, &mut NoopDelay).unwrap();
+    current.apply_config(&config, &mut NoopDelay).unwrap();
+    legacy.transmit(&frame).unwrap();
+    current.transmit(&frame).unwrap();
+    current.rx_status().unwrap();
+
+    legacy.spi.done();
+    current.spi.done();
+}
+
+#[test]
+fn test_typestate_transitions() {
+    let config = Config::default().bitrate(CNF_500K_BPS);
+    let mut transactions = apply_config(&config);
+    // CANCTRL is not written, the mode is requested by the transition
+    transactions.truncate(transactions.len() - 7);
+    transactions.extend(set_mode(0b000));
+    transactions.extend(read_canstat(0b000));
+    transactions.extend(read_status(0));
+    transactions.extend([
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Write as u8, 0x31]),
+        Transaction::write_vec(vec![0, 32, 0, 0, 3, 1, 2, 3]),
+        Transaction::transaction_end(),
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Rts as u8 | 1]),
+        Transaction::transaction_end(),
+    ]);
+    // Sleep is requested, the controller stays in NormalOperation
+    transactions.extend(set_mode(0b001));
+    transactions.extend(read_canstat(0b000));
+    let bus = Mock::new(&transactions);
+
+    let mut mcp25xx =
+        typestate::MCP25xx::new(MCP25xx::with_variant(bus, Variant::MCP2510), &mut NoopDelay)
+            .unwrap();
+    // invalid bit timings are rejected without writing anything
+    let err = mcp25xx.set_bitrate(CNF::default()).unwrap_err();
+    assert!(matches!(err, Error::InvalidBitTiming(_)));
+    let err = mcp25xx.configure(&Config::default()).unwrap_err();
+    assert!(matches!(err, Error::InvalidBitTiming(_)));
+    mcp25xx.configure(&config).unwrap();
+    let mut mcp25xx = mcp25xx
+        .into_mode::<typestate::Normal>(&mut NoopDelay, 10)
+        .unwrap();
+
+    let frame = CanFrame::new(StandardId::new(1).unwrap(), &[1, 2, 3]).unwrap();
+    mcp25xx.transmit(&frame).unwrap();
+
+    let Err(mut error) = mcp25xx.into_mode::<typestate::Sleep>(&mut NoopDelay, 0) else {
+        panic!("controller did not enter Sleep mode");
+    };
+    assert!(matches!(
+        error.error,
+        Error::ModeTimeout(OperationMode::NormalOperation)
+    ));
+    error.mcp25xx.spi.done();
+}
+
+#[test]
+fn test_typestate_bus_off_recovery() {
+    let config = Config::default()
+        .bitrate(CNF_500K_BPS)
+        .bus_off_recovery(BusOffRecovery::Manual);
+    let mut transactions = apply_config(&config);
+    transactions.truncate(transactions.len() - 7);
+    transactions.extend(
+        [
+            set_mode(0b000),
+            read_canstat(0b000),
+            // bus-off seen by receive, the controller is held in Configuration mode
+            read_intf(0b0010_0000),
+            read_eflg(0b0010_0000),
+            read_status(0),
+            set_mode(0b100),
+            read_canstat(0b100),
+            // rejoin
+            set_mode(0b000),
+            read_canstat(0b000),
+            read_intf(0),
+        ]
+        .concat(),
+    );
+    let bus = Mock::new(&transactions);
+
+    let mut mcp25xx = typestate::MCP25xx::new(MCP25xx::new(bus), &mut NoopDelay).unwrap();
+    mcp25xx.configure(&config).unwrap();
+    let mut mcp25xx = mcp25xx
+        .into_mode::<typestate::Normal>(&mut NoopDelay, 10)
+        .unwrap();
+
+    let err = mcp25xx.receive().unwrap_err();
+    assert!(matches!(err, nb::Error::Other(Error::BusOff)));
+    mcp25xx.recover_from_bus_off(&mut NoopDelay, 1).unwrap();
+    assert!(matches!(mcp25xx.receive(), Err(nb::Error::WouldBlock)));
+    mcp25xx.release().spi.done();
+}
+
+#[test]
+fn test_bit_timing_calculation() {
+    use mcp25xx::bitrates::{BitTimingError, DEFAULT_SAMPLE_POINT, calculate};
+
+    let timing = calculate(16_000_000, 500_000, DEFAULT_SAMPLE_POINT, 1).unwrap();
+    // 16 time quanta: SyncSeg 1, PropSeg 7, PS1 6, PS2 2
+    assert_eq!(timing.cnf.into_bytes(), [0x01, 0xAE, 0x00]);
+    assert_eq!(timing.error_ppm, 0);
+    assert_eq!(timing.sample_point, 875);
+
+    let timing = calculate(25_000_000, 1_000_000, DEFAULT_SAMPLE_POINT, 1).unwrap();
+    assert_eq!(timing.error_ppm, 38_461);
+
+    // PS2 needs to be longer than SJW
+    let timing = calculate(16_000_000, 500_000, DEFAULT_SAMPLE_POINT, 4).unwrap();
+    let cnf = timing.cnf;
+    assert!(cnf.cnf3.phseg2() + 1 > cnf.cnf1.sjw() + 1);
+
+    assert_eq!(
+        calculate(8_000_000, 1_000_000, DEFAULT_SAMPLE_POINT, 1).unwrap_err(),
+        BitTimingError::NoSolution
+    );
+    assert_eq!(
+        calculate(16_000_000, 500_000, DEFAULT_SAMPLE_POINT, 5).unwrap_err(),
+        BitTimingError::InvalidSjw
+    );
+}
+
+#[test]
+fn test_apply_config_rejects_invalid_bit_timing() {
+    use mcp25xx::bitrates::TimingViolation;
+
+    // nothing gets written
+    let mut mock = MCP25xx::new(Mock::new(&[]));
+    let config = Config::default().mode(OperationMode::NormalOperation);
+    assert!(matches!(
+        mock.apply_config(&config, &mut NoopDelay),
+        Err(Error::InvalidBitTiming(TimingViolation::TimeQuanta(5)))
+    ));
+    mock.spi.done();
+}
+
+fn clear_detection_flags() -> Vec<Transaction<u8>> {
+    vec![
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![
+            Instruction::BitModify as u8,
+            CANINTF::ADDRESS,
+            0b1000_0011,
+            0,
+        ]),
+        Transaction::transaction_end(),
+    ]
+}
+
+fn try_bitrate(cnf: CNF, intf: &[u8]) -> Vec<Transaction<u8>> {
+    let mut transactions = vec![
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Write as u8, CNF3::ADDRESS]),
+        Transaction::write_vec(cnf.into_bytes().to_vec()),
+        Transaction::transaction_end(),
+    ];
+    transactions.extend(set_mode(0b011));
+    transactions.extend(read_canstat(0b011));
+    for &flags in intf {
+        transactions.extend(read_intf(flags));
+    }
+    transactions.extend(set_mode(0b100));
+    transactions.extend(read_canstat(0b100));
+    transactions.extend(clear_detection_flags());
+    transactions
+}
+
+#[test]
+fn test_detect_bitrate() {
+    use mcp25xx::bitrates::clock_8mhz::{CNF_125K_BPS, CNF_250K_BPS};
+
+    let mut transactions = set_mode(0b100);
+    transactions.extend(read_canstat(0b100));
+    // filters set before are bypassed while detecting
+    transactions.extend(read_register(RXB0CTRL::ADDRESS, 0b0000_0100));
+    transactions.extend(read_register(RXB1CTRL::ADDRESS, 0b0000_0000));
+    transactions.extend(write_register(RXB0CTRL::ADDRESS, 0b0110_0100));
+    transactions.extend(write_register(RXB1CTRL::ADDRESS, 0b0110_0000));
+    transactions.extend(clear_detection_flags());
+    // message error right away
+    transactions.extend(try_bitrate(CNF_500K_BPS, &[0b1000_0000]));
+    // frames received within the window
+    transactions.extend(try_bitrate(CNF_250K_BPS, &[0, 0b01, 0b11]));
+    transactions.extend(write_register(RXB0CTRL::ADDRESS, 0b0000_0100));
+    transactions.extend(write_register(RXB1CTRL::ADDRESS, 0b0000_0000));
+    let mut mock = MCP25xx::new(Mock::new(&transactions));
+
+    let candidates = [CNF_500K_BPS, CNF_250K_BPS, CNF_125K_BPS];
+    let cnf = mock
+        .detect_bitrate(&candidates, &mut NoopDelay, 2, 1)
+        .unwrap()
+        .unwrap();
+    assert_eq!(cnf.into_bytes(), CNF_250K_BPS.into_bytes());
+    mock.spi.done();
+}
+
+fn read_config(bytes: [u8; 36], canctrl: u8, rxb0ctrl: u8, rxb1ctrl: u8) -> Vec<Transaction<u8>> {
+    let read = |address, bytes: &[u8]| {
+        vec![
+            Transaction::transaction_start(),
+            Transaction::write_vec(vec![Instruction::Read as u8, address]),
+            Transaction::read_vec(bytes.to_vec()),
+            Transaction::transaction_end(),
+        ]
+    };
+    [
+        read(0x00, &bytes[..12]),
+        read(0x10, &bytes[12..24]),
+        read(0x20, &bytes[24..]),
+        read(CANCTRL::ADDRESS, &[canctrl]),
+        read(RXB0CTRL::ADDRESS, &[rxb0ctrl]),
+        read(RXB1CTRL::ADDRESS, &[rxb1ctrl]),
+    ]
+    .concat()
+}
+
+#[test]
+fn test_apply_config_verified() {
+    let config = Config::default()
+        .mode(OperationMode::NormalOperation)
+        .bitrate(CNF_500K_BPS)
+        .receive_buffer_0(RXB0CTRL::default().with_rxm(RXM::ReceiveAny))
+        .interrupts(CANINTE::new().with_rx0ie(true).with_errie(true));
+    let canctrl = u8::from(config.canctrl);
+    let mut bytes = [0; 36];
+    bytes[32..35].copy_from_slice(&CNF_500K_BPS.into_bytes());
+    bytes[35] = 0b0010_0001;
+    // Filter0 holds a stale value, which the configuration does not write
+    bytes[..4].copy_from_slice(&[0x24, 0x60, 0x00, 0x00]);
+
+    // the filter hit and the copy of bukt are read-only
+    let transactions = [
+        apply_config(&config),
+        read_config(bytes, canctrl, 0x63, 0x05),
+    ]
+    .concat();
+    let mut mcp25xx = MCP25xx::new(Mock::new(&transactions));
+    mcp25xx
+        .apply_config_verified(&config, &mut NoopDelay)
+        .unwrap();
+    mcp25xx.spi.done();
+
+    let mut mcp25xx = MCP25xx::new(Mock::new(&read_config(bytes, canctrl, 0x63, 0x05)));
+    let registers = mcp25xx.read_config().unwrap();
+    mcp25xx.spi.done();
+    assert_eq!(
+        registers.filters[0].1.id(),
+        Id::Standard(StandardId::new(0x123).unwrap())
+    );
+    assert_eq!(registers.cnf.into_bytes(), CNF_500K_BPS.into_bytes());
+    assert!(registers.caninte.errie());
+
+    // CNF1 and RXB1CTRL do not hold what was written
+    bytes[34] ^= 0x01;
+    let transactions = [
+        apply_config(&config),
+        read_config(bytes, canctrl, 0x60, 0x20),
+    ]
+    .concat();
+    let mut mcp25xx = MCP25xx::new(Mock::new(&transactions));
+    let Err(VerifyError::Mismatch { written, read }) =
+        mcp25xx.apply_config_verified(&config, &mut NoopDelay)
+    else {
+        panic!("mismatch not reported");
+    };
+    mcp25xx.spi.done();
+    let mismatches: Vec<RegisterMismatch> = read.mismatches(&written).collect();
+    assert_eq!(
+        mismatches,
+        [
+            RegisterMismatch {
+                address: CNF1::ADDRESS,
+                written: 0x00,
+                read: 0x01
+            },
+            RegisterMismatch {
+                address: RXB1CTRL::ADDRESS,
+                written: 0x00,
+                read: 0x20
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_register_blocks() {
+    assert_eq!(TXBnCTRL(TxBuffer::TXB1).address(), TXB1CTRL::ADDRESS);
+    assert_eq!(TXBnFRAME(TxBuffer::TXB2).address(), 0x51);
+    assert_eq!(RXBnCTRL(RxBuffer::RXB1).address(), RXB1CTRL::ADDRESS);
+    assert_eq!(RXBnFRAME(RxBuffer::RXB0).address(), 0x61);
+    assert_eq!(TXBnFRAME::LEN, RXBnFRAME::LEN);
+    assert_eq!(mcp25xx::AcceptanceFilter::Mask1.address(), 0x24);
+    assert_eq!(CNF_500K_BPS.address(), CNF3::ADDRESS);
+    assert_eq!(
+        usize::from(CNF1::ADDRESS - CNF3::ADDRESS) + 1,
+        <CNF as RegisterBlock>::LEN
+    );
+}
+
+#[test]
+fn test_snapshot() {
+    let mut bytes = vec![0; 128];
+    bytes[0x0E] = 0x80;
+    bytes[0x0F] = 0x87;
+    bytes[0x28..0x2B].copy_from_slice(&CNF_500K_BPS.into_bytes());
+    bytes[0x2C] = 0x01;
+    bytes[0x61..0x64].copy_from_slice(&[0x24, 0x60, 0x00]);
+    let transactions = [
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Read as u8, 0x00]),
+        Transaction::read_vec(bytes.clone()),
+        Transaction::transaction_end(),
+    ];
+    let mut spi = Mock::new(&transactions);
+    let mut mcp25xx = MCP25xx::new(spi.clone());
+    let snapshot = mcp25xx.snapshot().unwrap();
+    spi.done();
+
+    assert_eq!(snapshot.bytes[..], bytes[..]);
+    assert_eq!(
+        snapshot.register::<CANSTAT>().opmod(),
+        OperationMode::Configuration
+    );
+    assert_eq!(snapshot.cnf().into_bytes(), CNF_500K_BPS.into_bytes());
+    assert!(snapshot.register::<CANINTF>().rx0if());
+    assert_eq!(
+        snapshot.rx_frame(RxBuffer::RXB0).id(),
+        Id::Standard(StandardId::new(0x123).unwrap())
+    );
+    let dump = snapshot.to_string();
+    assert_eq!(dump.lines().count(), 28);
+    assert!(dump.starts_with("0x0e CANSTAT    80 "));
+    assert!(dump.contains("0x2c CANINTF    01 "));
+
+    let hex = format!("{snapshot:x}");
+    assert_eq!(hex.len(), 256);
+    assert_eq!(RegisterSnapshot::from_hex(&hex), Some(snapshot));
+    assert_eq!(
+        RegisterSnapshot::from_hex(&hex.to_uppercase()),
+        Some(snapshot)
+    );
+    assert_eq!(RegisterSnapshot::from_hex(&hex[2..]), None);
+
+    let mut after = snapshot;
+    after.bytes[0x2C] = 0x00;
+    after.bytes[0x1D] = 0x08;
+    after.bytes[0x3E] = 0x40;
+    let changes: Vec<_> = snapshot.diff(&after).map(|c| c.to_string()).collect();
+    assert_eq!(
+        changes,
+        ["REC (0x1d): 0x00 -> 0x08", "CANINTF (0x2c): 0x01 -> 0x00"]
+    );
+
+    assert_eq!(RegisterName(0x01).to_string(), "RXF0SIDL");
+    assert_eq!(RegisterName(0x1A).to_string(), "RXF5EID8");
+    assert_eq!(RegisterName(0x27).to_string(), "RXM1EID0");
+    assert_eq!(RegisterName(0x4D).to_string(), "TXB1D7");
+    assert_eq!(RegisterName(0x75).to_string(), "RXB1DLC");
+}
+
+fn read_register(address: u8, value: u8) -> Vec<Transaction<u8>> {
+    vec![
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Read as u8, address]),
+        Transaction::read_vec(vec![value]),
+        Transaction::transaction_end(),
+    ]
+}
+
+fn write_register(address: u8, value: u8) -> Vec<Transaction<u8>> {
+    vec![
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Write as u8, address, value]),
+        Transaction::transaction_end(),
+    ]
+}
+
+fn bit_modify(address: u8, mask: u8) -> Vec<Transaction<u8>> {
+    vec![
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::BitModify as u8, address, mask, 0x00]),
+        Transaction::transaction_end(),
+    ]
+}
+
+#[test]
+fn test_handle_interrupt() {
+    let transactions = [
+        // error interrupt with an overflow of RXB1
+        read_register(CANSTAT::ADDRESS, 0b1000_0010),
+        read_register(EFLG::ADDRESS, 0b1000_0001),
+        bit_modify(EFLG::ADDRESS, 0b1000_0000),
+        bit_modify(CANINTF::ADDRESS, 0b0010_0000),
+        // TXB1 sent
+        read_register(CANSTAT::ADDRESS, 0b1000_1000),
+        bit_modify(CANINTF::ADDRESS, 0b0000_1000),
+        // RXB0 full, its flag is left for reading the buffer
+        read_register(CANSTAT::ADDRESS, 0b1000_1100),
+        // message error
+        read_register(CANSTAT::ADDRESS, 0b1000_0000),
+        read_register(CANINTF::ADDRESS, 0b1000_0001),
+        bit_modify(CANINTF::ADDRESS, 0b1000_0000),
+        // nothing pending
+        read_register(CANSTAT::ADDRESS, 0b1000_0000),
+        read_register(CANINTF::ADDRESS, 0b0000_0001),
+    ]
+    .concat();
+    let mut mcp25xx = MCP25xx::new(Mock::new(&transactions));
+
+    let Some(InterruptEvent::Error(eflg)) = mcp25xx.handle_interrupt().unwrap() else {
+        panic!("error not reported");
+    };
+    assert!(eflg.rx1ovr() && eflg.ewarn());
+    assert!(matches!(
+        mcp25xx.handle_interrupt().unwrap(),
+        Some(InterruptEvent::TxComplete(TxBuffer::TXB1))
+    ));
+    assert!(matches!(
+        mcp25xx.handle_interrupt().unwrap(),
+        Some(InterruptEvent::RxReady(RxBuffer::RXB0))
+    ));
+    assert!(matches!(
+        mcp25xx.handle_interrupt().unwrap(),
+        Some(InterruptEvent::MessageError)
+    ));
+    assert!(mcp25xx.handle_interrupt().unwrap().is_none());
+    mcp25xx.spi.done();
+}
+
+#[test]
+fn test_tx_status() {
+    let transactions = [
+        // TXB0 pending
+        read_status(0b0000_0100),
+        vec![
+            Transaction::transaction_start(),
+            Transaction::write_vec(vec![Instruction::Write as u8, 0x41]),
+            Transaction::write_vec(vec![0, 32, 0, 0, 3, 1, 2, 3]),
+            Transaction::transaction_end(),
+            Transaction::transaction_start(),
+            Transaction::write_vec(vec![Instruction::Rts as u8 | 2]),
+            Transaction::transaction_end(),
+        ],
+        read_register(0x40, 0b0000_1000),
+        // retried after losing arbitration and a bus error
+        read_register(0x40, 0b0011_1000),
+        // lost arbitration once, then sent
+        read_register(0x40, 0b0010_0000),
+        read_intf(0b0000_1000),
+        // failed in one-shot mode
+        read_register(0x40, 0b0010_0000),
+        read_intf(0b0000_0000),
+        read_register(0x40, 0b0001_0000),
+        read_intf(0b0000_0000),
+        read_register(0x40, 0b0100_0000),
+        read_intf(0b0000_0000),
+        // sent, the controller set tx1if
+        read_register(0x40, 0b0000_0000),
+        read_intf(0b0000_1000),
+        // TXB2 never held a frame
+        read_register(0x50, 0b0000_0000),
+        read_intf(0b0000_1000),
+    ]
+    .concat();
+    let mut mcp25xx = MCP25xx::with_variant(Mock::new(&transactions), Variant::MCP2510);
+
+    let frame = CanFrame::new(StandardId::new(1).unwrap(), &[1, 2, 3]).unwrap();
+    let transmission = mcp25xx.transmit_tracked(&frame).unwrap();
+    assert!(matches!(transmission.buffer, TxBuffer::TXB1));
+    assert!(transmission.replaced.is_none());
+
+    let statuses: Vec<TxStatus> = (0..7)
+        .map(|_| mcp25xx.tx_status(transmission.buffer).unwrap())
+        .collect();
+    assert_eq!(
+        statuses,
+        [
+            TxStatus::Pending,
+            TxStatus::Pending,
+            TxStatus::Sent,
+            TxStatus::LostArbitration,
+            TxStatus::Error,
+            TxStatus::Aborted,
+            TxStatus::Sent,
+        ]
+    );
+    assert_eq!(mcp25xx.tx_status(TxBuffer::TXB2).unwrap(), TxStatus::Idle);
+    mcp25xx.spi.done();
+}
+
+fn read_tx_frame(buf_idx: u8, id: u8) -> Vec<Transaction<u8>> {
+    vec![
+        Transaction::transaction_start(),
+        Transaction::write_vec(vec![Instruction::Read as u8, 0x31 + 0x10 * buf_idx]),
+        Transaction::read_vec(vec![0, id << 5, 0, 0, 1, 9, 0, 0, 0, 0, 0, 0, 0]),
+        Transaction::transaction_end(),
+    ]
+}
+
+#[test]
+fn test_abort() {
+    let transactions = [
+        // aborted
+        read_register(0x40, 0b0000_1000),
+        bit_modify(0x40, 0b0000_1000),
+        read_register(0x40, 0b0100_0000),
+        read_tx_frame(1, 7),
+        // not pending, the abort flag is left from before
+        read_register(0x50, 0b0100_0000),
+        // sent before it could be aborted
+        read_register(0x30, 0b0000_1000),
+        bit_modify(0x30, 0b0000_1000),
+        read_register(0x30, 0b0000_1000),
+        read_register(0x30, 0b0000_0000),
+        // the controller stopped responding
+        read_register(0x30, 0b0000_1000),
+        bit_modify(0x30, 0b0000_1000),
+        read_register(0x30, 0b0000_1000),
+    ]
+    .concat();
+    let mut mcp25xx = MCP25xx::new(Mock::new(&transactions));
+
+    let mut abort = |buf_idx| mcp25xx.abort(buf_idx, &mut NoopDelay, 1);
+    let frame = abort(TxBuffer::TXB1).unwrap().unwrap();
+    assert_eq!(frame.id(), Id::Standard(StandardId::new(7).unwrap()));
+    assert_eq!(frame.data(), &[9]);
+    assert!(abort(TxBuffer::TXB2).unwrap().is_none());
+    assert!(abort(TxBuffer::TXB0).unwrap().is_none());
+    // without time to wait, TXBnCTRL is read once
+    let err = mcp25xx
+        .abort(TxBuffer::TXB0, &mut NoopDelay, 0)
+        .unwrap_err();
+    assert!(matches!(err, Error::AbortTimeout(TxBuffer::TXB0)));
+    mcp25xx.spi.done();
+}
+
+#[test]
+fn test_abort_all() {
+    let transactions = [
+        // TXB1 and TXB2 pending
+        read_status(0b0101_0000),
+        vec![
+            Transaction::transaction_start(),
+            Transaction::write_vec(vec![
+                Instruction::BitModify as u8,
+                CANCTRL::ADDRESS,
+                0b0001_0000,
+                0b0001_0000,
+            ]),
+            Transaction::transaction_end(),
+        ],
+        // TXB2 is being transmitted
+        read_status(0b0100_0000),
+        read_status(0b0000_0000),
+        bit_modify(CANCTRL::ADDRESS, 0b0001_0000),
+        read_register(0x40, 0b0100_0000),
+        read_tx_frame(1, 5),
+        read_register(0x50, 0b0000_0000),
+    ]
+    .concat();
+    let mut mcp25xx = MCP25xx::new(Mock::new(&transactions));
+
+    let [txb0, txb1, txb2] = mcp25xx.abort_all(&mut NoopDelay, 1).unwrap();
+    assert!(txb0.is_none());
+    assert_eq!(
+        txb1.unwrap().id(),
+        Id::Standard(StandardId::new(5).unwrap())
+    );
+    assert!(txb2.is_none());
+    mcp25xx.spi.done();
+}
+
+#[test]
+fn test_abort_all_timeout() {
+    let transactions = [
+        read_status(0b0000_0100),
+        vec![
+            Transaction::transaction_start(),
+            Transaction::write_vec(vec![
+                Instruction::BitModify as u8,
+                CANCTRL::ADDRESS,
+                0b0001_0000,
+                0b0001_0000,
+            ]),
+            Transaction::transaction_end(),
+        ],
+        // TXB0 stays pending, abat is cleared anyway
+        read_status(0b0000_0100),
+        bit_modify(CANCTRL::ADDRESS, 0b0001_0000),
+    ]
+    .concat();
+    let mut mcp25xx = MCP25xx::new(Mock::new(&transactions));
+
+    let err = mcp25xx.abort_all(&mut NoopDelay, 0).unwrap_err();
+    assert!(matches!(err, Error::AbortTimeout(TxBuffer::TXB0)));
+    mcp25xx.spi.done();
+}